@@ -1,3 +1,4 @@
+use crate::bus::Bus;
 use crate::system::ExecutionError;
 /*
 $FF10	NR10	Sound channel 1 sweep	R/W	All
@@ -21,22 +22,963 @@ $FF23	NR44	Sound channel 4 control	Mixed	All
 $FF24	NR50	Master volume & VIN panning	R/W	All
 $FF25	NR51	Sound panning	R/W	All
 $FF26	NR52	Sound on/off	Mixed	All
+$FF30	$FF3F	Wave pattern RAM, 32 4 bit samples, high nibble first
+
+every channel makes a 4 bit number, which goes through its own DAC to become a voltage. NR51 picks which side
+each one goes to and NR50 scales each side. a channel can be off while its DAC is still on, which just outputs a
+flat level. that DC offset is why real hardware has a capacitor on the output, we fake that with a high pass.
+
+the frame sequencer ticks at 512Hz and clocks the slow stuff:
+Step   Length Ctr  Vol Env     Sweep
+0      Clock       -           -
+1      -           -           -
+2      Clock       -           Clock
+3      -           -           -
+4      Clock       -           -
+5      -           -           -
+6      Clock       -           Clock
+7      -           Clock       -
 */
 
-#[derive(Default, Debug)]
+//T cycles per second, everything below is counted in these
+const CPU_HZ: u32 = 4_194_304;
+const FRAME_SEQUENCER_CYCLES: u32 = CPU_HZ / 512;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+//bit n is the output at duty position n
+const DUTY_PATTERNS: [u8; 4] = [0b1000_0000, 0b1000_0001, 0b1110_0001, 0b0111_1110];
+const NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+#[derive(Debug, Clone, Copy)]
+struct Length {
+    counter: u16,
+    enabled: bool,
+    max: u16,
+}
+
+impl Length {
+    fn new(max: u16) -> Self {
+        Self {
+            counter: 0,
+            enabled: false,
+            max,
+        }
+    }
+
+    //the register holds how much has already elapsed, not how much is left
+    fn load(&mut self, value: u16) {
+        self.counter = self.max - value;
+    }
+
+    //true when this just ran out and the channel should turn off
+    fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+
+    //bit 6 of NRx4. extra_clock is set when the next frame sequencer step wont clock length, in which case turning
+    //length on clocks it once right away. true when that ran it out and the channel should turn off
+    fn write_control(&mut self, value: u8, extra_clock: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = (value & 0b0100_0000) != 0;
+        if extra_clock && !was_enabled && self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            //triggering in the same write reloads it anyway
+            return self.counter == 0 && (value & 0b1000_0000) == 0;
+        }
+        false
+    }
+
+    fn trigger(&mut self, extra_clock: bool) {
+        if self.counter == 0 {
+            self.counter = self.max;
+            if self.enabled && extra_clock {
+                self.counter -= 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Envelope {
+    initial: u8,
+    increase: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn read(&self) -> u8 {
+        (self.initial << 4) | ((self.increase as u8) << 3) | self.period
+    }
+
+    fn write(&mut self, value: u8) {
+        self.initial = value >> 4;
+        self.increase = (value & 0b1000) != 0;
+        self.period = value & 0b111;
+        //a period going from 0 to something without a trigger would otherwise start counting down from 0
+        self.reload();
+    }
+
+    fn reload(&mut self) {
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    //the top 5 bits of NRx2 double as the DAC power
+    fn dac_on(&self) -> bool {
+        self.initial != 0 || self.increase
+    }
+
+    fn trigger(&mut self) {
+        self.volume = self.initial;
+        self.reload();
+    }
+
+    fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    enabled: bool,
+    shadow: u16,
+    //clearing negate after a subtraction was done kills the channel
+    negate_used: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pulse {
+    enabled: bool,
+    duty: u8,
+    position: u8,
+    frequency: u16,
+    timer: u32,
+    length: Length,
+    envelope: Envelope,
+    //only channel 1 has one
+    sweep: Option<Sweep>,
+}
+
+impl Pulse {
+    fn new(has_sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            duty: 0,
+            position: 0,
+            frequency: 0,
+            timer: 0,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+            sweep: if has_sweep {
+                Some(Sweep::default())
+            } else {
+                None
+            },
+        }
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 4
+    }
+
+    fn step(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles > 0 {
+            if self.timer > cycles {
+                self.timer -= cycles;
+                return;
+            }
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) & 7;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        ((DUTY_PATTERNS[self.duty as usize] >> self.position) & 1) * self.envelope.volume
+    }
+
+    fn trigger(&mut self, extra_clock: bool) {
+        self.enabled = self.envelope.dac_on();
+        self.timer = self.period();
+        self.envelope.trigger();
+        self.length.trigger(extra_clock);
+
+        if let Some(sweep) = self.sweep.as_mut() {
+            sweep.shadow = self.frequency;
+            sweep.timer = if sweep.period == 0 { 8 } else { sweep.period };
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+            sweep.negate_used = false;
+            if sweep.shift != 0 {
+                self.sweep_calculate();
+            }
+        }
+    }
+
+    //the next frequency, turning the channel off if it would overflow
+    fn sweep_calculate(&mut self) -> u16 {
+        let sweep = self.sweep.as_mut().unwrap();
+        let delta = sweep.shadow >> sweep.shift;
+        let frequency = if sweep.negate {
+            sweep.negate_used = true;
+            sweep.shadow - delta
+        } else {
+            sweep.shadow + delta
+        };
+        if frequency > 2047 {
+            self.enabled = false;
+        }
+        frequency
+    }
+
+    fn clock_sweep(&mut self) {
+        let Some(sweep) = self.sweep.as_mut() else {
+            return;
+        };
+        if sweep.timer > 0 {
+            sweep.timer -= 1;
+        }
+        if sweep.timer != 0 {
+            return;
+        }
+        sweep.timer = if sweep.period == 0 { 8 } else { sweep.period };
+        if !sweep.enabled || sweep.period == 0 {
+            return;
+        }
+
+        let frequency = self.sweep_calculate();
+        let sweep = self.sweep.as_mut().unwrap();
+        if frequency <= 2047 && sweep.shift != 0 {
+            sweep.shadow = frequency;
+            self.frequency = frequency;
+            //it checks again with the new value straight away, but only to see if it overflows
+            self.sweep_calculate();
+        }
+    }
+
+    fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => match &self.sweep {
+                Some(sweep) => {
+                    0b1000_0000 | (sweep.period << 4) | ((sweep.negate as u8) << 3) | sweep.shift
+                }
+                None => 0xFF,
+            },
+            1 => (self.duty << 6) | 0b0011_1111,
+            2 => self.envelope.read(),
+            3 => 0xFF,
+            4 => 0b1011_1111 | ((self.length.enabled as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8, extra_clock: bool) {
+        match register {
+            0 => {
+                if let Some(sweep) = self.sweep.as_mut() {
+                    sweep.period = (value >> 4) & 0b111;
+                    sweep.shift = value & 0b111;
+                    let negate = (value & 0b1000) != 0;
+                    if sweep.negate && !negate && sweep.negate_used {
+                        self.enabled = false;
+                    }
+                    sweep.negate = negate;
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load((value & 0b0011_1111) as u16);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_on() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (((value & 0b111) as u16) << 8);
+                if self.length.write_control(value, extra_clock) {
+                    self.enabled = false;
+                }
+                if (value & 0b1000_0000) != 0 {
+                    self.trigger(extra_clock);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Wave {
+    enabled: bool,
+    dac_on: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: u32,
+    position: u8,
+    //the nibble last read out of wave ram, this is what actually plays
+    sample: u8,
+    ram: [u8; 16],
+    length: Length,
+}
+
+impl Wave {
+    fn new() -> Self {
+        Self {
+            enabled: false,
+            dac_on: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            ram: [0; 16],
+            length: Length::new(256),
+        }
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 2
+    }
+
+    fn step(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles > 0 {
+            if self.timer > cycles {
+                self.timer -= cycles;
+                return;
+            }
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) & 31;
+            let byte = self.ram[(self.position / 2) as usize];
+            self.sample = if self.position % 2 == 0 {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        //mute, 100%, 50%, 25%
+        let shift = [4, 0, 1, 2][self.volume_code as usize];
+        self.sample >> shift
+    }
+
+    fn trigger(&mut self, extra_clock: bool) {
+        self.enabled = self.dac_on;
+        self.position = 0;
+        //theres a short delay before the first sample gets read
+        self.timer = self.period() + 6;
+        self.length.trigger(extra_clock);
+    }
+
+    //while the channel is playing the cpu only gets at the byte the channel is on. real DMG only allows that on the
+    //exact cycle the channel reads it and gives 0xFF otherwise, we dont go that fine grained
+    fn ram_index(&self, offset: usize) -> usize {
+        if self.enabled {
+            (self.position / 2) as usize
+        } else {
+            offset
+        }
+    }
+
+    fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => 0b0111_1111 | ((self.dac_on as u8) << 7),
+            1 => 0xFF,
+            2 => 0b1001_1111 | (self.volume_code << 5),
+            3 => 0xFF,
+            4 => 0b1011_1111 | ((self.length.enabled as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8, extra_clock: bool) {
+        match register {
+            0 => {
+                self.dac_on = (value & 0b1000_0000) != 0;
+                if !self.dac_on {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(value as u16),
+            2 => self.volume_code = (value >> 5) & 0b11,
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (((value & 0b111) as u16) << 8);
+                if self.length.write_control(value, extra_clock) {
+                    self.enabled = false;
+                }
+                if (value & 0b1000_0000) != 0 {
+                    self.trigger(extra_clock);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Noise {
+    enabled: bool,
+    lfsr: u16,
+    clock_shift: u8,
+    //7 bit mode, which sounds more like a tone
+    short_mode: bool,
+    divisor_code: u8,
+    timer: u32,
+    length: Length,
+    envelope: Envelope,
+}
+
+impl Noise {
+    fn new() -> Self {
+        Self {
+            enabled: false,
+            lfsr: 0x7FFF,
+            clock_shift: 0,
+            short_mode: false,
+            divisor_code: 0,
+            timer: 0,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+        }
+    }
+
+    fn period(&self) -> u32 {
+        NOISE_DIVISORS[self.divisor_code as usize] << self.clock_shift
+    }
+
+    fn step(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles > 0 {
+            if self.timer > cycles {
+                self.timer -= cycles;
+                return;
+            }
+            cycles -= self.timer;
+            self.timer = self.period();
+            //shifts of 14 and 15 never clock the lfsr
+            if self.clock_shift < 14 {
+                let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+                self.lfsr = (self.lfsr >> 1) | (bit << 14);
+                if self.short_mode {
+                    self.lfsr = (self.lfsr & !(1 << 6)) | (bit << 6);
+                }
+            }
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        ((!self.lfsr & 1) as u8) * self.envelope.volume
+    }
+
+    fn trigger(&mut self, extra_clock: bool) {
+        self.enabled = self.envelope.dac_on();
+        self.lfsr = 0x7FFF;
+        self.timer = self.period();
+        self.envelope.trigger();
+        self.length.trigger(extra_clock);
+    }
+
+    fn read(&self, register: u16) -> u8 {
+        match register {
+            0 | 1 => 0xFF,
+            2 => self.envelope.read(),
+            3 => (self.clock_shift << 4) | ((self.short_mode as u8) << 3) | self.divisor_code,
+            4 => 0b1011_1111 | ((self.length.enabled as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8, extra_clock: bool) {
+        match register {
+            0 => {}
+            1 => self.length.load((value & 0b0011_1111) as u16),
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_on() {
+                    self.enabled = false;
+                }
+            }
+            3 => {
+                self.clock_shift = value >> 4;
+                self.short_mode = (value & 0b1000) != 0;
+                self.divisor_code = value & 0b111;
+            }
+            4 => {
+                if self.length.write_control(value, extra_clock) {
+                    self.enabled = false;
+                }
+                if (value & 0b1000_0000) != 0 {
+                    self.trigger(extra_clock);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug)]
 pub struct Audio {
-    //TODO:
-    //how to best structure these? maybe a custom struct for each channel?
+    power: bool,
+    ch1: Pulse,
+    ch2: Pulse,
+    ch3: Wave,
+    ch4: Noise,
+    NR50: u8,
+    NR51: u8,
+    frame_timer: u32,
+    //the step the frame sequencer runs next
+    frame_step: u8,
+
+    //output side. we average everything between two samples, then take the DC offset out
+    sample_rate: u32,
+    sample_timer: u32,
+    accumulated: (f32, f32),
+    accumulated_count: u32,
+    capacitor: (f32, f32),
+    capacitor_charge: f32,
+    //interleaved left, right
+    samples: Vec<f32>,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
 }
 
 impl Audio {
-    pub fn read(&mut self, address: u16, len: usize) -> Result<Vec<u8>, ExecutionError> {
-        warn!("reading from unimplemented audio. returning 0x00s");
-        return Ok(vec![0; len]);
+    pub fn new(sample_rate: u32) -> Self {
+        let mut audio = Self {
+            power: false,
+            ch1: Pulse::new(true),
+            ch2: Pulse::new(false),
+            ch3: Wave::new(),
+            ch4: Noise::new(),
+            NR50: 0,
+            NR51: 0,
+            frame_timer: 0,
+            frame_step: 0,
+            sample_rate,
+            sample_timer: 0,
+            accumulated: (0.0, 0.0),
+            accumulated_count: 0,
+            capacitor: (0.0, 0.0),
+            capacitor_charge: 0.0,
+            samples: Vec::new(),
+        };
+        audio.set_sample_rate(sample_rate);
+        audio
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        //the real capacitor leaks this much per T cycle, scaled up to one of our samples
+        self.capacitor_charge = 0.999958f32.powf(CPU_HZ as f32 / sample_rate as f32);
+    }
+
+    //everything produced since the last call, interleaved left and right
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    //runs the apu for some number of T cycles, a M cycle at a time
+    pub fn tick(&mut self, cycles: usize) {
+        for _ in 0..cycles / 4 {
+            self.step();
+        }
+    }
+
+    fn step(&mut self) {
+        if self.power {
+            self.frame_timer += 4;
+            if self.frame_timer >= FRAME_SEQUENCER_CYCLES {
+                self.frame_timer -= FRAME_SEQUENCER_CYCLES;
+                self.clock_frame_sequencer();
+            }
+            self.ch1.step(4);
+            self.ch2.step(4);
+            self.ch3.step(4);
+            self.ch4.step(4);
+        }
+
+        let (left, right) = self.mix();
+        self.accumulated.0 += left;
+        self.accumulated.1 += right;
+        self.accumulated_count += 1;
+
+        self.sample_timer += self.sample_rate * 4;
+        if self.sample_timer >= CPU_HZ {
+            self.sample_timer -= CPU_HZ;
+            self.emit_sample();
+        }
+    }
+
+    fn clock_frame_sequencer(&mut self) {
+        if self.frame_step % 2 == 0 {
+            if self.ch1.length.clock() {
+                self.ch1.enabled = false;
+            }
+            if self.ch2.length.clock() {
+                self.ch2.enabled = false;
+            }
+            if self.ch3.length.clock() {
+                self.ch3.enabled = false;
+            }
+            if self.ch4.length.clock() {
+                self.ch4.enabled = false;
+            }
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.ch1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.ch1.envelope.clock();
+            self.ch2.envelope.clock();
+            self.ch4.envelope.clock();
+        }
+        self.frame_step = (self.frame_step + 1) & 7;
+    }
+
+    //both sides, roughly -1 to 1
+    fn mix(&self) -> (f32, f32) {
+        let dac = |on: bool, digital: u8| {
+            if on {
+                1.0 - digital as f32 / 7.5
+            } else {
+                0.0
+            }
+        };
+        let channels = [
+            dac(self.ch1.envelope.dac_on(), self.ch1.output()),
+            dac(self.ch2.envelope.dac_on(), self.ch2.output()),
+            dac(self.ch3.dac_on, self.ch3.output()),
+            dac(self.ch4.envelope.dac_on(), self.ch4.output()),
+        ];
+
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (i, level) in channels.iter().enumerate() {
+            if (self.NR51 & (0b1_0000 << i)) != 0 {
+                left += level;
+            }
+            if (self.NR51 & (0b1 << i)) != 0 {
+                right += level;
+            }
+        }
+        let left_volume = (((self.NR50 >> 4) & 0b111) + 1) as f32 / 8.0;
+        let right_volume = ((self.NR50 & 0b111) + 1) as f32 / 8.0;
+        (left * left_volume / 4.0, right * right_volume / 4.0)
+    }
+
+    fn emit_sample(&mut self) {
+        let count = self.accumulated_count.max(1) as f32;
+        let left = self.accumulated.0 / count;
+        let right = self.accumulated.1 / count;
+        self.accumulated = (0.0, 0.0);
+        self.accumulated_count = 0;
+
+        let out_left = left - self.capacitor.0;
+        let out_right = right - self.capacitor.1;
+        self.capacitor.0 = left - out_left * self.capacitor_charge;
+        self.capacitor.1 = right - out_right * self.capacitor_charge;
+
+        //if nobody is taking samples dont hang on to more than a second of them
+        if self.samples.len() >= self.sample_rate as usize * 2 {
+            self.samples.drain(..2);
+        }
+        self.samples.push(out_left);
+        self.samples.push(out_right);
+    }
+
+    //turning the apu off clears every register and ignores writes to them until it comes back on. wave ram and
+    //(on DMG) the length counters survive
+    fn power_off(&mut self) {
+        let lengths = (
+            self.ch1.length.counter,
+            self.ch2.length.counter,
+            self.ch3.length.counter,
+            self.ch4.length.counter,
+        );
+        let wave_ram = self.ch3.ram;
+
+        self.ch1 = Pulse::new(true);
+        self.ch2 = Pulse::new(false);
+        self.ch3 = Wave::new();
+        self.ch4 = Noise::new();
+        self.NR50 = 0;
+        self.NR51 = 0;
+
+        self.ch1.length.counter = lengths.0;
+        self.ch2.length.counter = lengths.1;
+        self.ch3.length.counter = lengths.2;
+        self.ch4.length.counter = lengths.3;
+        self.ch3.ram = wave_ram;
+        self.power = false;
+    }
+
+    fn read_nr52(&self) -> u8 {
+        ((self.power as u8) << 7)
+            | 0b0111_0000
+            | ((self.ch4.enabled as u8) << 3)
+            | ((self.ch3.enabled as u8) << 2)
+            | ((self.ch2.enabled as u8) << 1)
+            | (self.ch1.enabled as u8)
+    }
+}
+
+impl Bus for Audio {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
+        let value = match address {
+            0xFF10..=0xFF14 => self.ch1.read(address - 0xFF10),
+            0xFF15..=0xFF19 => self.ch2.read(address - 0xFF15),
+            0xFF1A..=0xFF1E => self.ch3.read(address - 0xFF1A),
+            0xFF1F..=0xFF23 => self.ch4.read(address - 0xFF1F),
+            0xFF24 => self.NR50,
+            0xFF25 => self.NR51,
+            0xFF26 => self.read_nr52(),
+            //nothing is hooked up between NR52 and wave ram
+            0xFF27..=0xFF2F => 0xFF,
+            0xFF30..=0xFF3F => self.ch3.ram[self.ch3.ram_index((address - 0xFF30) as usize)],
+            _ => unreachable!("audio trying to service a READ outside of its memory mapping range"),
+        };
+        Ok(value)
+    }
+
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
+        //while powered off only NR52, wave ram and (on DMG) the length timers can be written
+        if !self.power && !matches!(address, 0xFF26 | 0xFF30..=0xFF3F) {
+            match address {
+                0xFF11 => self.ch1.length.load((value & 0b0011_1111) as u16),
+                0xFF16 => self.ch2.length.load((value & 0b0011_1111) as u16),
+                0xFF1B => self.ch3.length.load(value as u16),
+                0xFF20 => self.ch4.length.load((value & 0b0011_1111) as u16),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        let extra_clock = self.frame_step % 2 == 1;
+        match address {
+            0xFF10..=0xFF14 => self.ch1.write(address - 0xFF10, value, extra_clock),
+            0xFF15..=0xFF19 => self.ch2.write(address - 0xFF15, value, extra_clock),
+            0xFF1A..=0xFF1E => self.ch3.write(address - 0xFF1A, value, extra_clock),
+            0xFF1F..=0xFF23 => self.ch4.write(address - 0xFF1F, value, extra_clock),
+            0xFF24 => self.NR50 = value,
+            0xFF25 => self.NR51 = value,
+            0xFF26 => {
+                let power = (value & 0b1000_0000) != 0;
+                if self.power && !power {
+                    self.power_off();
+                } else if !self.power && power {
+                    self.power = true;
+                    self.frame_timer = 0;
+                    self.frame_step = 0;
+                }
+            }
+            0xFF27..=0xFF2F => {}
+            0xFF30..=0xFF3F => {
+                let index = self.ch3.ram_index((address - 0xFF30) as usize);
+                self.ch3.ram[index] = value;
+            }
+            _ => {
+                unreachable!("audio trying to service a WRITE outside of its memory mapping range")
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn powered() -> Audio {
+        let mut audio = Audio::default();
+        audio.write8(0xFF26, 0x80).unwrap();
+        audio
+    }
+
+    //runs the frame sequencer through some number of its steps
+    fn run_steps(audio: &mut Audio, steps: u32) {
+        audio.tick((FRAME_SEQUENCER_CYCLES * steps) as usize);
+    }
+
+    fn nr52(audio: &mut Audio) -> u8 {
+        audio.read8(0xFF26).unwrap()
+    }
+
+    #[test]
+    fn envelope_clocks_at_64hz() {
+        let mut audio = powered();
+        //volume 15, going down every envelope clock
+        audio.write8(0xFF12, 0xF1).unwrap();
+        audio.write8(0xFF14, 0x80).unwrap();
+        assert_eq!(audio.ch1.envelope.volume, 15);
+        run_steps(&mut audio, 8);
+        assert_eq!(audio.ch1.envelope.volume, 14);
+        run_steps(&mut audio, 8);
+        assert_eq!(audio.ch1.envelope.volume, 13);
+
+        //volume 0, going up every other envelope clock, and it stops at 15
+        audio.write8(0xFF17, 0x0A).unwrap();
+        audio.write8(0xFF19, 0x80).unwrap();
+        run_steps(&mut audio, 8 * 4);
+        assert_eq!(audio.ch2.envelope.volume, 2);
+        run_steps(&mut audio, 8 * 40);
+        assert_eq!(audio.ch2.envelope.volume, 15);
+    }
+
+    #[test]
+    fn envelope_period_written_without_trigger() {
+        let mut envelope = Envelope::default();
+        envelope.volume = 15;
+        envelope.write(0xF3);
+        for _ in 0..3 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume, 14);
+    }
+
+    #[test]
+    fn sweep_moves_frequency() {
+        let mut audio = powered();
+        //every sweep clock, add frequency / 2
+        audio.write8(0xFF10, 0x11).unwrap();
+        audio.write8(0xFF12, 0xF0).unwrap();
+        audio.write8(0xFF13, 0x00).unwrap();
+        audio.write8(0xFF14, 0x81).unwrap();
+        assert_eq!(audio.ch1.frequency, 0x100);
+        //the first sweep clock is on step 2
+        run_steps(&mut audio, 3);
+        assert_eq!(audio.ch1.frequency, 0x180);
+        run_steps(&mut audio, 4);
+        assert_eq!(audio.ch1.frequency, 0x240);
+        assert_eq!(nr52(&mut audio) & 0b0001, 0b0001);
+    }
+
+    #[test]
+    fn sweep_overflow_disables() {
+        let mut audio = powered();
+        audio.write8(0xFF10, 0x11).unwrap();
+        audio.write8(0xFF12, 0xF0).unwrap();
+        audio.write8(0xFF13, 0xFF).unwrap();
+        //the check on trigger already overflows
+        audio.write8(0xFF14, 0x87).unwrap();
+        assert_eq!(nr52(&mut audio) & 0b0001, 0);
+
+        //subtracting never overflows, but clearing negate after using it kills the channel
+        audio.write8(0xFF10, 0x19).unwrap();
+        audio.write8(0xFF14, 0x87).unwrap();
+        assert_eq!(nr52(&mut audio) & 0b0001, 0b0001);
+        audio.write8(0xFF10, 0x11).unwrap();
+        assert_eq!(nr52(&mut audio) & 0b0001, 0);
+    }
+
+    #[test]
+    fn length_turns_channel_off() {
+        let mut audio = powered();
+        //1 step of length left, with length enabled
+        audio.write8(0xFF11, 0x3F).unwrap();
+        audio.write8(0xFF12, 0xF0).unwrap();
+        audio.write8(0xFF14, 0xC0).unwrap();
+        assert_eq!(nr52(&mut audio) & 0b0001, 0b0001);
+        run_steps(&mut audio, 1);
+        assert_eq!(nr52(&mut audio) & 0b0001, 0);
+
+        //without length enabled it plays forever
+        audio.write8(0xFF16, 0x3F).unwrap();
+        audio.write8(0xFF17, 0xF0).unwrap();
+        audio.write8(0xFF19, 0x80).unwrap();
+        run_steps(&mut audio, 64);
+        assert_eq!(nr52(&mut audio) & 0b0010, 0b0010);
+
+        //the wave channel counts 256. we are on an odd step, so enabling length takes one off right away
+        assert_eq!(audio.frame_step % 2, 1);
+        audio.write8(0xFF1A, 0x80).unwrap();
+        audio.write8(0xFF1B, 0x00).unwrap();
+        audio.write8(0xFF1E, 0xC0).unwrap();
+        run_steps(&mut audio, 2 * 254);
+        assert_eq!(nr52(&mut audio) & 0b0100, 0b0100);
+        run_steps(&mut audio, 2);
+        assert_eq!(nr52(&mut audio) & 0b0100, 0);
+    }
+
+    #[test]
+    fn dac_off_stops_channel() {
+        let mut audio = powered();
+        audio.write8(0xFF21, 0xF0).unwrap();
+        audio.write8(0xFF23, 0x80).unwrap();
+        assert_eq!(nr52(&mut audio) & 0b1000, 0b1000);
+        audio.write8(0xFF21, 0x00).unwrap();
+        assert_eq!(nr52(&mut audio) & 0b1000, 0);
+        //and it doesnt come back on a trigger while the dac is off
+        audio.write8(0xFF23, 0x80).unwrap();
+        assert_eq!(nr52(&mut audio) & 0b1000, 0);
+    }
+
+    #[test]
+    fn power_off_clears_registers() {
+        let mut audio = powered();
+        audio.write8(0xFF24, 0x77).unwrap();
+        audio.write8(0xFF30, 0x12).unwrap();
+        audio.write8(0xFF26, 0x00).unwrap();
+        assert_eq!(audio.read8(0xFF24).unwrap(), 0x00);
+        assert_eq!(nr52(&mut audio), 0x70);
+        //wave ram survives and stays writable, nothing else takes writes
+        assert_eq!(audio.read8(0xFF30).unwrap(), 0x12);
+        audio.write8(0xFF24, 0x77).unwrap();
+        assert_eq!(audio.read8(0xFF24).unwrap(), 0x00);
     }
 
-    pub fn write(&mut self, address: u16, data: &[u8]) -> Result<usize, ExecutionError> {
-        warn!("writing to unimplmented audio. no effects");
-        return Ok(data.len());
+    #[test]
+    fn unused_registers_read_high() {
+        let mut audio = powered();
+        for address in 0xFF27..=0xFF2F {
+            audio.write8(address, 0x00).unwrap();
+            assert_eq!(audio.read8(address).unwrap(), 0xFF);
+        }
+        assert_eq!(audio.read8(0xFF15).unwrap(), 0xFF);
+        assert_eq!(audio.read8(0xFF1F).unwrap(), 0xFF);
     }
 }
@@ -0,0 +1,379 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+//wherever the apu's samples end up. they always come in as interleaved left, right f32s
+pub trait AudioSink: Send {
+    fn sample_rate(&self) -> u32;
+    fn push(&mut self, samples: &[f32]);
+    //how much faster or slower than sample_rate we should be producing right now. sinks that run on their own
+    //clock use this to keep from running dry or backing up
+    fn rate_adjustment(&self) -> f32 {
+        1.0
+    }
+    //called once when we are shutting down
+    fn finish(&mut self) {}
+}
+
+//no audio device, or nobody wants sound. the samples just go nowhere
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        crate::audio::DEFAULT_SAMPLE_RATE
+    }
+
+    fn push(&mut self, _samples: &[f32]) {}
+}
+
+/*16 bit stereo PCM .wav. the header is written up front with empty sizes and patched in finish(), so the file
+is only valid once we shut down cleanly. always runs at a fixed rate, so the same rom and inputs make the same file*/
+pub struct WavRecorder {
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    data_bytes: u32,
+    //stop trying after the first failed write, no point spamming the log
+    failed: bool,
+    finished: bool,
+}
+
+impl WavRecorder {
+    pub fn create(path: &Path, sample_rate: u32) -> std::io::Result<Self> {
+        let mut recorder = Self {
+            writer: BufWriter::new(File::create(path)?),
+            sample_rate,
+            data_bytes: 0,
+            failed: false,
+            finished: false,
+        };
+        recorder.write_header()?;
+        Ok(recorder)
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let w = &mut self.writer;
+        w.write_all(b"RIFF")?;
+        w.write_all(&(36 + self.data_bytes).to_le_bytes())?;
+        w.write_all(b"WAVE")?;
+        w.write_all(b"fmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        //PCM, 2 channels
+        w.write_all(&1u16.to_le_bytes())?;
+        w.write_all(&2u16.to_le_bytes())?;
+        w.write_all(&self.sample_rate.to_le_bytes())?;
+        //bytes per second, then bytes per frame, then bits per sample
+        w.write_all(&(self.sample_rate * 4).to_le_bytes())?;
+        w.write_all(&4u16.to_le_bytes())?;
+        w.write_all(&16u16.to_le_bytes())?;
+        w.write_all(b"data")?;
+        w.write_all(&self.data_bytes.to_le_bytes())
+    }
+
+    fn patch_header(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.flush()
+    }
+}
+
+impl AudioSink for WavRecorder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        if self.failed {
+            return;
+        }
+        for sample in samples {
+            //the RIFF sizes are 32 bit and the outer one counts the rest of the header too, so a wav tops out
+            //just under 4GB (about 6.7 hours at 44.1kHz). end the recording there rather than wrap the sizes
+            let Some(data_bytes) = self
+                .data_bytes
+                .checked_add(2)
+                .filter(|bytes| bytes.checked_add(36).is_some())
+            else {
+                warn!("audio recording hit the wav size limit, stopping it here");
+                self.failed = true;
+                return;
+            };
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            if let Err(e) = self.writer.write_all(&value.to_le_bytes()) {
+                warn!("failed to write audio recording, giving up on it: {e}");
+                self.failed = true;
+                return;
+            }
+            self.data_bytes = data_bytes;
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        match self.patch_header() {
+            Ok(()) => info!(
+                "finished audio recording, {} bytes of samples",
+                self.data_bytes
+            ),
+            Err(e) => warn!("failed to finish audio recording: {e}"),
+        }
+    }
+}
+
+impl Drop for WavRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+//how much audio we keep queued up for the device, and how far off the nominal rate we are allowed to drift to
+//keep it half full. half a percent is not something anyone can hear
+const LIVE_BUFFER_MS: usize = 100;
+const MAX_RATE_DELTA: f32 = 0.005;
+
+/*plays through the default output device. the device pulls samples on its own clock, which is never exactly the
+rate we make them at, so the queue would slowly drain (crackle) or fill up (lag). instead we nudge the apu's
+output rate up or down a little depending on how full the queue is, which keeps it hovering around half*/
+pub struct LiveSink {
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    //in f32s, so 2 per frame
+    capacity: usize,
+    sample_rate: u32,
+}
+
+impl LiveSink {
+    //the stream has to stay alive for as long as we want sound, and cant leave the thread it was made on, so that
+    //goes back to the frontend. None means there is no usable audio device and we run without sound
+    pub fn open() -> Option<(LiveSink, cpal::Stream)> {
+        let host = cpal::default_host();
+        let Some(device) = host.default_output_device() else {
+            warn!("no audio output device, running without sound");
+            return None;
+        };
+        let supported = match device.default_output_config() {
+            Ok(supported) => supported,
+            Err(e) => {
+                warn!("couldnt get an audio output config, running without sound: {e}");
+                return None;
+            }
+        };
+
+        let sample_rate = supported.sample_rate().0;
+        let config = supported.config();
+        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, buffer.clone()),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, buffer.clone()),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, buffer.clone()),
+            format => {
+                warn!("audio device wants {format:?} samples, running without sound");
+                return None;
+            }
+        };
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("couldnt open an audio stream, running without sound: {e}");
+                return None;
+            }
+        };
+        if let Err(e) = stream.play() {
+            warn!("couldnt start the audio stream, running without sound: {e}");
+            return None;
+        }
+
+        info!(
+            "playing audio at {}Hz on {}",
+            sample_rate,
+            device
+                .name()
+                .unwrap_or_else(|_| "unknown device".to_string())
+        );
+        let sink = LiveSink {
+            buffer,
+            capacity: sample_rate as usize * 2 * LIVE_BUFFER_MS / 1000,
+            sample_rate,
+        };
+        Some((sink, stream))
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let mut last = (0.0f32, 0.0f32);
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut buffer = buffer.lock().unwrap();
+            for frame in data.chunks_mut(channels) {
+                //if we ran dry hold the last sample instead of dropping to 0, which pops less
+                if buffer.len() >= 2 {
+                    last = (buffer.pop_front().unwrap(), buffer.pop_front().unwrap());
+                }
+                for (i, out) in frame.iter_mut().enumerate() {
+                    let value = match (channels, i) {
+                        (1, _) => (last.0 + last.1) / 2.0,
+                        (_, 0) => last.0,
+                        (_, 1) => last.1,
+                        _ => 0.0,
+                    };
+                    *out = T::from_sample(value);
+                }
+            }
+        },
+        |e| warn!("audio stream error: {e}"),
+        None,
+    )
+}
+
+impl AudioSink for LiveSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        let mut buffer = self.buffer.lock().unwrap();
+        //keep whole frames so left and right never swap
+        let room = self.capacity.saturating_sub(buffer.len()) & !1;
+        if samples.len() > room {
+            trace!(
+                "audio buffer full, dropping {} samples",
+                samples.len() - room
+            );
+        }
+        buffer.extend(&samples[..samples.len().min(room)]);
+    }
+
+    fn rate_adjustment(&self) -> f32 {
+        let fill = self.buffer.lock().unwrap().len() as f32 / self.capacity as f32;
+        1.0 + MAX_RATE_DELTA * (1.0 - 2.0 * fill)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("gb-audio-test-{}-{}.wav", name, std::process::id()))
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn wav_header_sizes() {
+        let path = scratch_path("header");
+        let mut recorder = WavRecorder::create(&path, 44100).unwrap();
+        recorder.push(&[0.0, 1.0, -1.0, 0.5]);
+        recorder.push(&[2.0, -2.0]);
+        recorder.finish();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 44 + 12);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4), 36 + 12);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&bytes, 16), 16);
+        assert_eq!(u16_at(&bytes, 20), 1);
+        assert_eq!(u16_at(&bytes, 22), 2);
+        assert_eq!(u32_at(&bytes, 24), 44100);
+        assert_eq!(u32_at(&bytes, 28), 44100 * 4);
+        assert_eq!(u16_at(&bytes, 32), 4);
+        assert_eq!(u16_at(&bytes, 34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), 12);
+
+        //out of range samples get clamped
+        let samples: Vec<i16> = bytes[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(
+            samples,
+            [0, i16::MAX, -i16::MAX, i16::MAX / 2, i16::MAX, -i16::MAX]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wav_stops_at_size_limit() {
+        let path = scratch_path("limit");
+        let mut recorder = WavRecorder::create(&path, 44100).unwrap();
+        //pretend we already wrote almost 4GB, there is room for exactly one more sample
+        recorder.data_bytes = u32::MAX - 36 - 3;
+        recorder.push(&[0.5, 0.5, 0.5]);
+        assert!(recorder.failed);
+        assert_eq!(recorder.data_bytes, u32::MAX - 36 - 1);
+        //and nothing else gets written after that
+        recorder.push(&[0.5]);
+        assert_eq!(recorder.data_bytes, u32::MAX - 36 - 1);
+
+        drop(recorder);
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 44 + 2);
+        assert_eq!(u32_at(&bytes, 4), u32::MAX - 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wav_header_patched_on_drop() {
+        let path = scratch_path("drop");
+        {
+            let mut recorder = WavRecorder::create(&path, 48000).unwrap();
+            recorder.push(&[0.25; 8]);
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 44 + 16);
+        assert_eq!(u32_at(&bytes, 4), 36 + 16);
+        assert_eq!(u32_at(&bytes, 40), 16);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    //no device needed to check the queue, the stream only ever sees the buffer
+    fn live_sink(capacity: usize) -> LiveSink {
+        LiveSink {
+            buffer: Arc::new(Mutex::new(VecDeque::new())),
+            capacity,
+            sample_rate: 48000,
+        }
+    }
+
+    #[test]
+    fn live_sink_rate_follows_fill() {
+        let mut sink = live_sink(100);
+        assert_eq!(sink.rate_adjustment(), 1.0 + MAX_RATE_DELTA);
+        sink.push(&[0.0; 50]);
+        assert_eq!(sink.rate_adjustment(), 1.0);
+        sink.push(&[0.0; 50]);
+        assert_eq!(sink.rate_adjustment(), 1.0 - MAX_RATE_DELTA);
+    }
+
+    #[test]
+    fn live_sink_drops_whole_frames_when_full() {
+        let mut sink = live_sink(9);
+        sink.push(&[0.0; 20]);
+        assert_eq!(sink.buffer.lock().unwrap().len(), 8);
+        sink.push(&[0.0; 2]);
+        assert_eq!(sink.buffer.lock().unwrap().len(), 8);
+    }
+}
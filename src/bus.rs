@@ -0,0 +1,20 @@
+use crate::system::ExecutionError;
+
+//anything that sits on the memory bus. everything is done a byte at a time since thats all the real bus can do,
+//the wider accesses are just built on top of that so nobody ever has to care about crossing region boundaries
+pub trait Bus {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError>;
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError>;
+
+    //little endian, low byte at address and high byte at address+1
+    fn read16(&mut self, address: u16) -> Result<u16, ExecutionError> {
+        let lower = self.read8(address)?;
+        let upper = self.read8(address.wrapping_add(1))?;
+        Ok((upper as u16) << 8 | lower as u16)
+    }
+    fn write16(&mut self, address: u16, value: u16) -> Result<(), ExecutionError> {
+        self.write8(address, (value & 0x00FF) as u8)?;
+        self.write8(address.wrapping_add(1), ((value & 0xFF00) >> 8) as u8)?;
+        Ok(())
+    }
+}
@@ -1,32 +1,245 @@
+use crate::bus::Bus;
+use crate::mapper::{new_mapper, Mapper, Rtc};
 use crate::system::ExecutionError;
 use std::{
     fmt::format,
     fs::{metadata, File},
     io::Read,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
+//how long ram has to sit untouched before we write it out. games tend to write a save a few bytes at a time over
+//a bunch of frames, so wait for them to finish rather than hitting the disk on every byte
+const SAVE_FLUSH_DELAY: Duration = Duration::from_secs(1);
+
+//the logo the boot rom compares the cart against, byte for byte the same as the copy at 0x00A8 in dmg.bin
+pub const NINTENDO_LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+//knobs for how picky we are when loading a rom
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    //load carts that would fail the boot rom checks (bad logo or header checksum) instead of erroring out.
+    //homebrew toolchains get the header wrong all the time, and the game itself usually doesnt care
+    pub lenient: bool,
+}
+
+//things wrong with the header that we noticed while loading
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderWarning {
+    HeaderChecksum { expected: u8, computed: u8 },
+    GlobalChecksum { expected: u16, computed: u16 },
+    LogoMismatch,
+}
+
+impl HeaderWarning {
+    //whether the real boot rom would lock up over this. nothing checks the global checksum
+    pub fn fails_boot(&self) -> bool {
+        match self {
+            HeaderWarning::HeaderChecksum { .. } | HeaderWarning::LogoMismatch => true,
+            HeaderWarning::GlobalChecksum { .. } => false,
+        }
+    }
+}
+
+impl std::fmt::Display for HeaderWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderWarning::HeaderChecksum { expected, computed } => write!(
+                f,
+                "header checksum failed, got: {:#04x}, expected: {:#04x}",
+                computed, expected
+            ),
+            HeaderWarning::GlobalChecksum { expected, computed } => write!(
+                f,
+                "global checksum failed, got: {:#06x}, expected: {:#06x}",
+                computed, expected
+            ),
+            HeaderWarning::LogoMismatch => write!(f, "nintendo logo doesnt match the boot rom"),
+        }
+    }
+}
+
 pub struct Cart {
     header: CartHeader,
-    rom: Vec<u8>,
-    ram: Vec<u8>,
+    mapper: Box<dyn Mapper>,
+    //where battery backed ram goes. None for carts without a battery, or ones that didnt come from a file
+    save_path: Option<PathBuf>,
+    //when ram was last written, if it has changed since the last flush
+    unsaved_since: Option<Instant>,
+    warnings: Vec<HeaderWarning>,
 }
 
 impl Cart {
-    pub fn new(rom: &mut std::fs::File) -> Result<Self, std::io::Error> {
+    pub fn new(rom: &mut impl Read, options: &LoadOptions) -> Result<Self, std::io::Error> {
         //let mut contents = vec![0; rom.metadata().unwrap().len() as usize];
         let mut contents = Vec::new();
         rom.read_to_end(&mut contents)?;
 
+        if contents.len() < 0x0150 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "rom is only {:#x} bytes, too short to have a header",
+                    contents.len()
+                ),
+            ));
+        }
         let header = CartHeader::new(contents[0x0100..=0x014F].try_into().unwrap())?;
+        info!("loaded cart:\n{}", header);
+
+        let warnings = header.verify(&contents);
+        for warning in &warnings {
+            if warning.fails_boot() && !options.lenient {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{warning} (load leniently to boot it anyway)"),
+                ));
+            }
+            warn!("{warning}");
+        }
+
+        //a short file is a bad dump and will read garbage once it banks past the end. a long one is usually just an
+        //overdump padded out to a power of 2, which does no harm
+        if contents.len() < header.rom_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "rom is {:#x} bytes but the header says it should be {:#x}",
+                    contents.len(),
+                    header.rom_size
+                ),
+            ));
+        } else if contents.len() > header.rom_size {
+            warn!(
+                "rom is {:#x} bytes but the header says it should be {:#x}, ignoring the extra",
+                contents.len(),
+                header.rom_size
+            );
+        }
+
+        let mapper = new_mapper(&header.cart_type, contents, header.ram_size)?;
 
-        //TODO: here we need to parse out allllllll our other ROM data
         Ok(Cart {
             header: header,
-            rom: Vec::new(),
-            ram: Vec::new(),
+            mapper,
+            save_path: None,
+            unsaved_since: None,
+            warnings,
         })
     }
+
+    //loads a rom from disk, along with its .sav sitting next to it if the cart has a battery.
+    //saves are named the same way as everyone else names them (game.gb -> game.sav) so they can be moved between emulators
+    pub fn from_file(path: &Path, options: &LoadOptions) -> Result<Self, std::io::Error> {
+        let mut cart = Cart::new(&mut File::open(path)?, options)?;
+        if !cart.header.cart_type.battery {
+            return Ok(cart);
+        }
+
+        let save_path = path.with_extension("sav");
+        match std::fs::read(&save_path) {
+            Ok(data) => {
+                info!("loading save from {}", save_path.display());
+                cart.load_save_data(&data);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("no save at {}, starting fresh", save_path.display());
+            }
+            Err(e) => return Err(e),
+        }
+        cart.save_path = Some(save_path);
+        Ok(cart)
+    }
+
+    pub fn warnings(&self) -> &[HeaderWarning] {
+        &self.warnings
+    }
+
+    //a cart that only got loaded because we were lenient. the boot rom would lock up on this one, so it needs to be
+    //started without it
+    pub fn fails_boot_checks(&self) -> bool {
+        self.warnings.iter().any(|w| w.fails_boot())
+    }
+
+    /*.sav layout, same as BGB/mGBA/SameBoy/VBA-M:
+    the external ram, byte for byte
+    the 48 byte RTC footer for MBC3 carts with a timer (see Rtc::to_footer)*/
+    fn save_data(&mut self) -> Vec<u8> {
+        let mut data = self.mapper.ram().to_vec();
+        if let Some(rtc) = self.mapper.rtc() {
+            data.extend(rtc.to_footer());
+        }
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let ram = self.mapper.ram_mut();
+        let ram_len = ram.len();
+        if data.len() < ram_len {
+            warn!(
+                "save is {:#x} bytes but the cart has {:#x} bytes of ram, loading what is there",
+                data.len(),
+                ram_len
+            );
+        }
+        let copy_len = ram_len.min(data.len());
+        ram[..copy_len].copy_from_slice(&data[..copy_len]);
+
+        //anything past the ram should be the clock
+        let footer = &data[copy_len..];
+        if let Some(rtc) = self.mapper.rtc() {
+            match Rtc::from_footer(footer) {
+                Some(loaded) => *rtc = loaded,
+                None => warn!("save has no usable rtc footer, starting the clock from zero"),
+            }
+        } else if !footer.is_empty() {
+            warn!(
+                "ignoring {:#x} extra bytes at the end of the save",
+                footer.len()
+            );
+        }
+    }
+
+    //writes battery ram out to the .sav if anything has changed since we last did. carts with a clock always get
+    //written, since the clock keeps moving even when the game never touches ram
+    pub fn flush_save(&mut self) {
+        if self.unsaved_since.is_none() && self.mapper.rtc().is_none() {
+            return;
+        }
+        let path = match &self.save_path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+
+        //write to a temp file and rename over the real one, so dying halfway through cant eat the save
+        let data = self.save_data();
+        let tmp_path = path.with_extension("sav.tmp");
+        let result =
+            std::fs::write(&tmp_path, &data).and_then(|_| std::fs::rename(&tmp_path, &path));
+        match result {
+            Ok(_) => {
+                debug!("flushed {:#x} bytes to {}", data.len(), path.display());
+                self.unsaved_since = None;
+            }
+            Err(e) => warn!("failed to write save to {}: {e}", path.display()),
+        }
+    }
+
+    //called from the run loop, flushes once the game has stopped writing to ram for a bit
+    pub fn tick_save(&mut self) {
+        if let Some(since) = self.unsaved_since {
+            if since.elapsed() >= SAVE_FLUSH_DELAY {
+                self.flush_save();
+            }
+        }
+    }
 }
+
 struct CartHeader {
     /*0100-0103 — Entry point
     After displaying the Nintendo logo, the built-in boot ROM jumps to the address $0100, which should then jump to the actual main program in the cartridge. Most commercial games fill this 4-byte area with a nop instruction followed by a jp $0150.
@@ -49,13 +262,15 @@ struct CartHeader {
 
     Parts of this area actually have a different meaning on later cartridges, reducing the actual title size to 15 ($0134–$0142) or 11 ($0134–$013E) characters; see below.
     */
-    title: [u8; (0x0144 - 0x134)],
+    title: String,
     /*013F-0142 — Manufacturer code
 
     In older cartridges these bytes were part of the Title (see above). In newer cartridges they contain a 4-character manufacturer code (in uppercase ASCII). The purpose of the manufacturer code is unknown.
 
+    there is no flag that says which one it is, so we only call it a manufacturer code on CGB carts where it is
+    4 uppercase ascii characters, and leave it as part of the title otherwise
     */
-    man_code: [u8; (0x0143 - 0x013F)],
+    man_code: Option<String>,
     /*0143 — CGB flag
 
     In older cartridges this byte was part of the Title (see above). The CGB and later models interpret this byte to decide whether to enable Color mode (“CGB Mode”) or to fall back to monochrome compatibility mode (“Non-CGB Mode”).
@@ -70,18 +285,15 @@ struct CartHeader {
     Research needed
 
     The PGB mode is not well researched or documented yet. Help is welcome!*/
-    cgb: u8,
-    /*
-    0144-0145 — New licensee code
-    This area contains a two-character ASCII “licensee code” indicating the game’s publisher. It is only meaningful if the Old licensee is exactly $33 (which is the case for essentially all games made after the SGB was released); otherwise, the old code must be considered.
-    */
-    new_lic_code: [u8; (0x0146 - 0x0144)],
+    cgb: CgbSupport,
     /*
     0146 — SGB flag
 
     This byte specifies whether the game supports SGB functions. The SGB will ignore any command packets if this byte is set to a value other than $03 (typically $00).
+
+    also needs the old licensee code to be $33, see below
      */
-    sgb_flag: u8,
+    sgb: bool,
     /*0147 — Cartridge type
 
     This byte indicates what kind of hardware is present on the cartridge — most notably its mapper.
@@ -116,7 +328,7 @@ struct CartHeader {
     $FF	HuC1+RAM+BATTERY
     1. No licensed cartridge makes use of this option. The exact behavior is unknown.
     2. MBC3 with 64 KiB of SRAM refers to MBC30, used only in Pocket Monsters: Crystal Version (the Japanese version of Pokémon Crystal Version).*/
-    cart_type: u8,
+    pub cart_type: CartType,
     /*0148 — ROM size
 
     This byte indicates how much ROM is present on the cartridge. In most cases, the ROM size is given by 32 KiB × (1 << <value>):
@@ -135,7 +347,8 @@ struct CartHeader {
     $54	1.5 MiB	96 3
 
     3. Only listed in unofficial docs. No cartridges or ROM files using these sizes are known. As the other ROM sizes are all powers of 2, these are likely inaccurate. The source of these values is unknown.*/
-    rom_size: u8,
+    //in bytes
+    pub rom_size: usize,
 
     /*0149 — RAM size
 
@@ -152,7 +365,8 @@ struct CartHeader {
     4. Listed in various unofficial docs as 2 KiB. However, a 2 KiB RAM chip was never used in a cartridge. The source of this value is unknown.
 
     Various “PD” ROMs (“Public Domain” homebrew ROMs, generally tagged with (PD) in the filename) are known to use the $01 RAM Size tag, but this is believed to have been a mistake with early homebrew tools, and the PD ROMs often don’t use cartridge RAM at all.*/
-    ram_size: u8,
+    //in bytes. unknown codes are treated as no ram
+    pub ram_size: usize,
 
     /*014A — Destination code
 
@@ -162,12 +376,15 @@ struct CartHeader {
     Code	Destination
     $00	Japan (and possibly overseas)
     $01	Overseas only*/
-    dest_code: u8,
+    destination: Destination,
 
     /*014B — Old licensee code
     This byte is used in older (pre-SGB) cartridges to specify the game’s publisher. However, the value $33 indicates that the New licensee codes must be considered instead. (The SGB will ignore any command packets unless this value is $33.)
+
+    0144-0145 — New licensee code
+    This area contains a two-character ASCII “licensee code” indicating the game’s publisher. It is only meaningful if the Old licensee is exactly $33 (which is the case for essentially all games made after the SGB was released); otherwise, the old code must be considered.
     */
-    old_lic_code: u8,
+    licensee: Licensee,
 
     /*014C — Mask ROM version number
 
@@ -197,6 +414,49 @@ struct CartHeader {
 
 impl CartHeader {
     pub fn new(contents: [u8; 0x50]) -> Result<Self, std::io::Error> {
+        let cgb = CgbSupport::from(contents[0x0143 - 0x0100]);
+        let (title, man_code) =
+            Self::decode_title(&contents[0x0134 - 0x0100..=0x0143 - 0x0100], cgb);
+
+        let old_lic_code = contents[0x014B - 0x0100];
+        let licensee = if old_lic_code == 0x33 {
+            Licensee::New([contents[0x0144 - 0x0100], contents[0x0145 - 0x0100]])
+        } else {
+            Licensee::Old(old_lic_code)
+        };
+
+        let rom_size_code = contents[0x0148 - 0x0100];
+        let rom_size = match rom_size_code {
+            0x00..=0x08 => 0x8000 << rom_size_code,
+            0x52 => 72 * 0x4000,
+            0x53 => 80 * 0x4000,
+            0x54 => 96 * 0x4000,
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("unknown rom size {:#04x} in header", rom_size_code),
+                ))
+            }
+        };
+
+        let ram_size_code = contents[0x0149 - 0x0100];
+        let ram_size = match ram_size_code {
+            0x00 => 0,
+            //officially unused, but some PD roms set it. treat it like the 2KiB chip the unofficial docs say it is
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            _ => {
+                warn!(
+                    "unknown ram size {:#02x} in header, assuming no ram",
+                    ram_size_code
+                );
+                0
+            }
+        };
+
         let header = Self {
             entry_point: contents[0x0100 - 0x0100..=0x0103 - 0x0100]
                 .try_into()
@@ -204,22 +464,15 @@ impl CartHeader {
             logo: contents[0x0104 - 0x0100..=0x0133 - 0x0100]
                 .try_into()
                 .unwrap(),
-            title: contents[0x0134 - 0x0100..=0x0143 - 0x0100]
-                .try_into()
-                .unwrap(),
-            man_code: contents[0x013F - 0x0100..=0x0142 - 0x0100]
-                .try_into()
-                .unwrap(),
-            cgb: contents[0x0143 - 0x0100],
-            new_lic_code: contents[0x0144 - 0x0100..=0x0145 - 0x0100]
-                .try_into()
-                .unwrap(),
-            sgb_flag: contents[0x0146 - 0x0100],
-            cart_type: contents[0x0147 - 0x0100],
-            rom_size: contents[0x0148 - 0x0100],
-            ram_size: contents[0x0149 - 0x0100],
-            dest_code: contents[0x014A - 0x0100],
-            old_lic_code: contents[0x014B - 0x0100],
+            title,
+            man_code,
+            cgb,
+            sgb: contents[0x0146 - 0x0100] == 0x03 && old_lic_code == 0x33,
+            cart_type: CartType::from(contents[0x0147 - 0x0100]),
+            rom_size,
+            ram_size,
+            destination: Destination::from(contents[0x014A - 0x0100]),
+            licensee,
             rom_version: contents[0x014C - 0x0100],
             header_checksum: contents[0x014D - 0x0100],
             global_checksum: contents[(0x014E - 0x0100)..=(0x014F - 0x0100)]
@@ -227,7 +480,17 @@ impl CartHeader {
                 .unwrap(),
         };
 
-        //println!("{:x}", contents[0x014D - 0x0100]);
+        Ok(header)
+    }
+
+    //runs the same checks as the boot rom, plus the global checksum that nothing on real hardware looks at.
+    //takes the whole rom since the global checksum covers all of it
+    pub fn verify(&self, rom: &[u8]) -> Vec<HeaderWarning> {
+        let mut warnings = Vec::new();
+
+        if self.logo != NINTENDO_LOGO {
+            warnings.push(HeaderWarning::LogoMismatch);
+        }
 
         /*
         uint8_t checksum = 0;
@@ -235,150 +498,768 @@ impl CartHeader {
             checksum = checksum - rom[address] - 1;
         } */
         let mut checksum: u8 = 0;
-        for address in (0x0134 - 0x0100)..=(0x014C - 0x0100) {
-            checksum = checksum.wrapping_sub(contents[address]).wrapping_sub(1);
+        for address in 0x0134..=0x014C {
+            checksum = checksum.wrapping_sub(rom[address]).wrapping_sub(1);
+        }
+        if checksum != self.header_checksum {
+            warnings.push(HeaderWarning::HeaderChecksum {
+                expected: self.header_checksum,
+                computed: checksum,
+            });
         }
 
-        if checksum != header.header_checksum {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "header checksum failed, got: {:x}, expected: {:x}",
-                    checksum, header.header_checksum,
-                ),
-            ));
+        let mut global: u16 = 0;
+        for (address, byte) in rom.iter().enumerate() {
+            if address != 0x014E && address != 0x014F {
+                global = global.wrapping_add(*byte as u16);
+            }
+        }
+        let expected = u16::from_be_bytes(self.global_checksum);
+        if global != expected {
+            warnings.push(HeaderWarning::GlobalChecksum {
+                expected,
+                computed: global,
+            });
         }
 
-        //TODO: do we actually care about the global checksum?
-        //i am not checking it rn
+        warnings
+    }
 
-        Ok(header)
+    //splits 0134-0143 into the title and (maybe) the manufacturer code.
+    //the old 16 character titles run all the way to 0143, CGB carts lose 0143 to the CGB flag, and
+    //newer CGB carts lose 013F-0142 to the manufacturer code on top of that
+    fn decode_title(bytes: &[u8], cgb: CgbSupport) -> (String, Option<String>) {
+        let to_string = |bytes: &[u8]| -> String {
+            bytes
+                .iter()
+                .take_while(|b| **b != 0)
+                .map(|b| {
+                    if b.is_ascii_graphic() || *b == b' ' {
+                        *b as char
+                    } else {
+                        '?'
+                    }
+                })
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        };
+
+        if cgb == CgbSupport::DmgOnly {
+            return (to_string(bytes), None);
+        }
+
+        let man_code = &bytes[0x013F - 0x0134..=0x0142 - 0x0134];
+        if man_code
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        {
+            (
+                to_string(&bytes[..0x013F - 0x0134]),
+                Some(to_string(man_code)),
+            )
+        } else {
+            (to_string(&bytes[..=0x0142 - 0x0134]), None)
+        }
     }
 }
 
-impl Cart {
-    pub fn read(&mut self, address: u16, len: usize) -> Result<Vec<u8>, ExecutionError> {
-        let value = match address {
-            //0100-0103 — Entry point
-            0x0100..=0x0103 => {
-                if len > 4 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                self.header.entry_point[address as usize..address as usize + len].to_vec()
-            }
-            //nintendo logo
-            0x0104..=0x0133 => {
-                let address: usize = (address - 0x0104) as usize;
-                self.header.logo[address..address + len as usize].to_vec()
-            }
-            //0134-0143 — Title
-            0x0134..=0x0143 => {
-                if len > (0x0144 - 0x0134) {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                let address: usize = (address - 0x0134) as usize;
-                self.header.title[address..address + len].to_vec()
-            }
-            //013F-0142 — Manufacturer code
-            //NOTE: you will need to fix this unreachable pattern based on mapper type somehow
-            0x013F..=0x0142 => {
-                if len > (0x0143 - 0x013F) {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                let address: usize = (address - 0x013F) as usize;
-                self.header.man_code[address..address + len].to_vec()
-            }
-            //0143 — CGB flag
-            //NOTE: same as above
-            0x0143 => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.cgb]
-            }
-            //0144-0145 — New licensee code
-            0x0144..=0x0145 => {
-                if len > (0x0146 - 0x0144) {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                let address: usize = (address - 0x0144) as usize;
-                self.header.new_lic_code[address..address + len].to_vec()
-            }
-            //0146 — SGB flag
-            0x0146 => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.sgb_flag]
-            }
-            //0147 — Cartridge type
-            0x0147 => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.cart_type]
-            }
-            //0148 — ROM size
-            0x0148 => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.rom_size]
-            }
-            //0149 — RAM size
-            0x0149 => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.ram_size]
-            }
-            //014A — Destination code
-            0x014A => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.dest_code]
-            }
-            //014B — Old licensee code
-            0x014B => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.old_lic_code]
-            }
-            //014C — Mask ROM version number
-            0x014C => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.rom_version]
-            }
-            //014D — Header checksum
-            0x014D => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                vec![self.header.header_checksum]
+impl std::fmt::Display for CartHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "title:       {}", self.title)?;
+        if let Some(man_code) = &self.man_code {
+            writeln!(f, "maker code:  {}", man_code)?;
+        }
+        writeln!(f, "type:        {}", self.cart_type)?;
+        writeln!(
+            f,
+            "rom:         {} KiB ({} banks)",
+            self.rom_size / 1024,
+            self.rom_size / 0x4000
+        )?;
+        writeln!(f, "ram:         {} KiB", self.ram_size / 1024)?;
+        writeln!(f, "cgb:         {}", self.cgb)?;
+        writeln!(f, "sgb:         {}", if self.sgb { "yes" } else { "no" })?;
+        writeln!(f, "destination: {}", self.destination)?;
+        writeln!(f, "publisher:   {}", self.licensee)?;
+        write!(f, "version:     {}", self.rom_version)
+    }
+}
+
+//the mapper half of the cart type byte
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapperKind {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    Tama5,
+    HuC3,
+    HuC1,
+    Unknown(u8),
+}
+
+impl std::fmt::Display for MapperKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapperKind::RomOnly => write!(f, "ROM"),
+            MapperKind::Mbc1 => write!(f, "MBC1"),
+            MapperKind::Mbc2 => write!(f, "MBC2"),
+            MapperKind::Mmm01 => write!(f, "MMM01"),
+            MapperKind::Mbc3 => write!(f, "MBC3"),
+            MapperKind::Mbc5 => write!(f, "MBC5"),
+            MapperKind::Mbc6 => write!(f, "MBC6"),
+            MapperKind::Mbc7 => write!(f, "MBC7"),
+            MapperKind::PocketCamera => write!(f, "POCKET CAMERA"),
+            MapperKind::Tama5 => write!(f, "BANDAI TAMA5"),
+            MapperKind::HuC3 => write!(f, "HuC3"),
+            MapperKind::HuC1 => write!(f, "HuC1"),
+            MapperKind::Unknown(code) => write!(f, "unknown ({:#04x})", code),
+        }
+    }
+}
+
+//0147 decoded into the mapper and whatever else is on the board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CartType {
+    pub mapper: MapperKind,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+    //only the MBC7 has one, an accelerometer
+    pub sensor: bool,
+}
+
+impl From<u8> for CartType {
+    fn from(value: u8) -> Self {
+        use MapperKind::*;
+        //mapper, ram, battery, timer, rumble
+        let (mapper, ram, battery, timer, rumble) = match value {
+            0x00 => (RomOnly, false, false, false, false),
+            0x01 => (Mbc1, false, false, false, false),
+            0x02 => (Mbc1, true, false, false, false),
+            0x03 => (Mbc1, true, true, false, false),
+            //MBC2 ram is inside the mapper, so the header doesnt count it as RAM
+            0x05 => (Mbc2, false, false, false, false),
+            0x06 => (Mbc2, false, true, false, false),
+            0x08 => (RomOnly, true, false, false, false),
+            0x09 => (RomOnly, true, true, false, false),
+            0x0B => (Mmm01, false, false, false, false),
+            0x0C => (Mmm01, true, false, false, false),
+            0x0D => (Mmm01, true, true, false, false),
+            0x0F => (Mbc3, false, true, true, false),
+            0x10 => (Mbc3, true, true, true, false),
+            0x11 => (Mbc3, false, false, false, false),
+            0x12 => (Mbc3, true, false, false, false),
+            0x13 => (Mbc3, true, true, false, false),
+            0x19 => (Mbc5, false, false, false, false),
+            0x1A => (Mbc5, true, false, false, false),
+            0x1B => (Mbc5, true, true, false, false),
+            0x1C => (Mbc5, false, false, false, true),
+            0x1D => (Mbc5, true, false, false, true),
+            0x1E => (Mbc5, true, true, false, true),
+            0x20 => (Mbc6, false, false, false, false),
+            0x22 => (Mbc7, true, true, false, true),
+            0xFC => (PocketCamera, false, false, false, false),
+            0xFD => (Tama5, false, false, false, false),
+            0xFE => (HuC3, false, false, false, false),
+            0xFF => (HuC1, true, true, false, false),
+            _ => (Unknown(value), false, false, false, false),
+        };
+        Self {
+            mapper,
+            ram,
+            battery,
+            timer,
+            rumble,
+            sensor: value == 0x22,
+        }
+    }
+}
+
+//same names as the table above, e.g. MBC3+TIMER+RAM+BATTERY
+impl std::fmt::Display for CartType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mapper)?;
+        if self.mapper == MapperKind::RomOnly && !self.ram {
+            write!(f, " ONLY")?;
+        }
+        if self.timer {
+            write!(f, "+TIMER")?;
+        }
+        if self.sensor {
+            write!(f, "+SENSOR")?;
+        }
+        if self.rumble {
+            write!(f, "+RUMBLE")?;
+        }
+        if self.ram {
+            write!(f, "+RAM")?;
+        }
+        if self.battery {
+            write!(f, "+BATTERY")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CgbSupport {
+    DmgOnly,
+    //runs on both, with extra color stuff on a CGB
+    Compatible,
+    CgbOnly,
+}
+
+impl From<u8> for CgbSupport {
+    fn from(value: u8) -> Self {
+        //the hardware only looks at bit 7, but C0 is what CGB only carts set
+        match value {
+            0xC0 => CgbSupport::CgbOnly,
+            v if (v & 0x80) != 0 => CgbSupport::Compatible,
+            _ => CgbSupport::DmgOnly,
+        }
+    }
+}
+
+impl std::fmt::Display for CgbSupport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CgbSupport::DmgOnly => write!(f, "DMG only"),
+            CgbSupport::Compatible => write!(f, "CGB enhanced, DMG compatible"),
+            CgbSupport::CgbOnly => write!(f, "CGB only"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+    Unknown(u8),
+}
+
+impl From<u8> for Destination {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => Destination::Japan,
+            0x01 => Destination::Overseas,
+            _ => Destination::Unknown(value),
+        }
+    }
+}
+
+impl std::fmt::Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Destination::Japan => write!(f, "Japan"),
+            Destination::Overseas => write!(f, "overseas"),
+            Destination::Unknown(code) => write!(f, "unknown ({:#04x})", code),
+        }
+    }
+}
+
+//which licensee code actually applies, the old one unless it is $33
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Licensee {
+    Old(u8),
+    New([u8; 2]),
+}
+
+impl Licensee {
+    pub fn publisher(&self) -> Option<&'static str> {
+        match self {
+            Licensee::Old(code) => old_licensee_name(*code),
+            Licensee::New(code) => new_licensee_name(code),
+        }
+    }
+}
+
+impl std::fmt::Display for Licensee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.publisher(), self) {
+            (Some(name), _) => write!(f, "{}", name),
+            (None, Licensee::Old(code)) => write!(f, "unknown ({:#04x})", code),
+            (None, Licensee::New(code)) => {
+                write!(f, "unknown ({})", String::from_utf8_lossy(code))
             }
-            //014E-014F — Global checksum
-            0x014E..=0x14F => {
-                if len > (0x150 - 0x14E) {
-                    return Err(ExecutionError::IllegalRead(address as usize));
+        }
+    }
+}
+
+fn new_licensee_name(code: &[u8; 2]) -> Option<&'static str> {
+    let name = match code {
+        b"00" => "None",
+        b"01" => "Nintendo R&D1",
+        b"08" => "Capcom",
+        b"13" => "Electronic Arts",
+        b"18" => "Hudson Soft",
+        b"19" => "b-ai",
+        b"20" => "kss",
+        b"22" => "pow",
+        b"24" => "PCM Complete",
+        b"25" => "san-x",
+        b"28" => "Kemco Japan",
+        b"29" => "seta",
+        b"30" => "Viacom",
+        b"31" => "Nintendo",
+        b"32" => "Bandai",
+        b"33" => "Ocean/Acclaim",
+        b"34" => "Konami",
+        b"35" => "Hector",
+        b"37" => "Taito",
+        b"38" => "Hudson",
+        b"39" => "Banpresto",
+        b"41" => "Ubi Soft",
+        b"42" => "Atlus",
+        b"44" => "Malibu",
+        b"46" => "angel",
+        b"47" => "Bullet-Proof",
+        b"49" => "irem",
+        b"50" => "Absolute",
+        b"51" => "Acclaim",
+        b"52" => "Activision",
+        b"53" => "American sammy",
+        b"54" => "Konami",
+        b"55" => "Hi tech entertainment",
+        b"56" => "LJN",
+        b"57" => "Matchbox",
+        b"58" => "Mattel",
+        b"59" => "Milton Bradley",
+        b"60" => "Titus",
+        b"61" => "Virgin",
+        b"64" => "LucasArts",
+        b"67" => "Ocean",
+        b"69" => "Electronic Arts",
+        b"70" => "Infogrames",
+        b"71" => "Interplay",
+        b"72" => "Broderbund",
+        b"73" => "sculptured",
+        b"75" => "sci",
+        b"78" => "THQ",
+        b"79" => "Accolade",
+        b"80" => "misawa",
+        b"83" => "lozc",
+        b"86" => "Tokuma Shoten Intermedia",
+        b"87" => "Tsukuda Original",
+        b"91" => "Chunsoft",
+        b"92" => "Video system",
+        b"93" => "Ocean/Acclaim",
+        b"95" => "Varie",
+        b"96" => "Yonezawa/s'pal",
+        b"97" => "Kaneko",
+        b"99" => "Pack in soft",
+        b"9H" => "Bottom Up",
+        b"A4" => "Konami (Yu-Gi-Oh!)",
+        b"BL" => "MTO",
+        b"DK" => "Kodansha",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn old_licensee_name(code: u8) -> Option<&'static str> {
+    let name = match code {
+        0x00 => "None",
+        0x01 => "Nintendo",
+        0x08 => "Capcom",
+        0x09 => "HOT-B",
+        0x0A => "Jaleco",
+        0x0B => "Coconuts Japan",
+        0x0C => "Elite Systems",
+        0x13 => "Electronic Arts",
+        0x18 => "Hudson Soft",
+        0x19 => "ITC Entertainment",
+        0x1A => "Yanoman",
+        0x1D => "Japan Clary",
+        0x1F => "Virgin Games",
+        0x24 => "PCM Complete",
+        0x25 => "San-X",
+        0x28 => "Kemco",
+        0x29 => "SETA Corporation",
+        0x30 => "Infogrames",
+        0x31 => "Nintendo",
+        0x32 => "Bandai",
+        0x34 => "Konami",
+        0x35 => "HectorSoft",
+        0x38 => "Capcom",
+        0x39 => "Banpresto",
+        0x3C => "Entertainment Interactive",
+        0x3E => "Gremlin",
+        0x41 => "Ubi Soft",
+        0x42 => "Atlus",
+        0x44 => "Malibu Interactive",
+        0x46 => "Angel",
+        0x47 => "Spectrum HoloByte",
+        0x49 => "Irem",
+        0x4A => "Virgin Games",
+        0x4D => "Malibu Interactive",
+        0x4F => "U.S. Gold",
+        0x50 => "Absolute",
+        0x51 => "Acclaim Entertainment",
+        0x52 => "Activision",
+        0x53 => "Sammy USA Corporation",
+        0x54 => "GameTek",
+        0x55 => "Park Place",
+        0x56 => "LJN",
+        0x57 => "Matchbox",
+        0x59 => "Milton Bradley Company",
+        0x5A => "Mindscape",
+        0x5B => "Romstar",
+        0x5C => "Naxat Soft",
+        0x5D => "Tradewest",
+        0x60 => "Titus Interactive",
+        0x61 => "Virgin Games",
+        0x67 => "Ocean Software",
+        0x69 => "Electronic Arts",
+        0x6E => "Elite Systems",
+        0x6F => "Electro Brain",
+        0x70 => "Infogrames",
+        0x71 => "Interplay Entertainment",
+        0x72 => "Broderbund",
+        0x73 => "Sculptured Software",
+        0x75 => "The Sales Curve Limited",
+        0x78 => "THQ",
+        0x79 => "Accolade",
+        0x7A => "Triffix Entertainment",
+        0x7C => "MicroProse",
+        0x7F => "Kemco",
+        0x80 => "Misawa Entertainment",
+        0x83 => "LOZC G.",
+        0x86 => "Tokuma Shoten",
+        0x8B => "Bullet-Proof Software",
+        0x8C => "Vic Tokai Corp.",
+        0x8E => "Ape Inc.",
+        0x8F => "I'Max",
+        0x91 => "Chunsoft Co.",
+        0x92 => "Video System",
+        0x93 => "Tsubaraya Productions",
+        0x95 => "Varie",
+        0x96 => "Yonezawa/S'Pal",
+        0x97 => "Kemco",
+        0x99 => "Arc",
+        0x9A => "Nihon Bussan",
+        0x9B => "Tecmo",
+        0x9C => "Imagineer",
+        0x9D => "Banpresto",
+        0x9F => "Nova",
+        0xA1 => "Hori Electric",
+        0xA2 => "Bandai",
+        0xA4 => "Konami",
+        0xA6 => "Kawada",
+        0xA7 => "Takara",
+        0xA9 => "Technos Japan",
+        0xAA => "Broderbund",
+        0xAC => "Toei Animation",
+        0xAD => "Toho",
+        0xAF => "Namco",
+        0xB0 => "Acclaim Entertainment",
+        0xB1 => "ASCII Corporation or Nexsoft",
+        0xB2 => "Bandai",
+        0xB4 => "Square Enix",
+        0xB6 => "HAL Laboratory",
+        0xB7 => "SNK",
+        0xB9 => "Pony Canyon",
+        0xBA => "Culture Brain",
+        0xBB => "Sunsoft",
+        0xBD => "Sony Imagesoft",
+        0xBF => "Sammy Corporation",
+        0xC0 => "Taito",
+        0xC2 => "Kemco",
+        0xC3 => "Square",
+        0xC4 => "Tokuma Shoten",
+        0xC5 => "Data East",
+        0xC6 => "Tonkin House",
+        0xC8 => "Koei",
+        0xC9 => "UFL",
+        0xCA => "Ultra Games",
+        0xCB => "VAP, Inc.",
+        0xCC => "Use Corporation",
+        0xCD => "Meldac",
+        0xCE => "Pony Canyon",
+        0xCF => "Angel",
+        0xD0 => "Taito",
+        0xD1 => "SOFEL",
+        0xD2 => "Quest",
+        0xD3 => "Sigma Enterprises",
+        0xD4 => "ASK Kodansha Co.",
+        0xD6 => "Naxat Soft",
+        0xD7 => "Copya System",
+        0xD9 => "Banpresto",
+        0xDA => "Tomy",
+        0xDB => "LJN",
+        0xDD => "Nippon Computer Systems",
+        0xDE => "Human Ent.",
+        0xDF => "Altron",
+        0xE0 => "Jaleco",
+        0xE1 => "Towa Chiki",
+        0xE2 => "Yutaka",
+        0xE3 => "Varie",
+        0xE5 => "Epoch",
+        0xE7 => "Athena",
+        0xE8 => "Asmik Ace Entertainment",
+        0xE9 => "Natsume",
+        0xEA => "King Records",
+        0xEB => "Atlus",
+        0xEC => "Epic/Sony Records",
+        0xEE => "IGS",
+        0xF0 => "A Wave",
+        0xF3 => "Extreme Entertainment",
+        0xFF => "LJN",
+        _ => return None,
+    };
+    Some(name)
+}
+
+impl Bus for Cart {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
+        match address {
+            0x0000..=0x7FFF => Ok(self.mapper.read_rom(address)),
+            0xA000..=0xBFFF => Ok(self.mapper.read_ram(address)),
+            _ => Err(ExecutionError::IllegalRead(address as usize)),
+        }
+    }
+
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
+        match address {
+            0x0000..=0x7FFF => self.mapper.write_register(address, value),
+            0xA000..=0xBFFF => {
+                self.mapper.write_ram(address, value);
+                if self.save_path.is_some() {
+                    self.unsaved_since = Some(Instant::now());
                 }
-                let address: usize = (address - 0x014E) as usize;
-                self.header.global_checksum[address..address + len].to_vec()
             }
-            _ => {
-                warn!("reading from cart region which is stubbed to just give back 0x00");
-                //return Err(ExecutionError::IllegalRead(address as usize));
-                vec![0; len]
-            }
-        };
-        return Ok(value.to_vec());
+            _ => return Err(ExecutionError::IllegalWrite(address as usize)),
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a rom that passes the boot checks, with each bank starting with its own number
+    fn test_rom(banks: usize, cart_type: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0; banks * 0x4000];
+        for bank in 0..banks {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x0134..0x0138].copy_from_slice(b"TEST");
+        rom[0x0147] = cart_type;
+        rom[0x0148] = (banks / 2).trailing_zeros() as u8;
+        rom[0x0149] = ram_size;
+        fix_header_checksum(&mut rom);
+        let mut global: u16 = 0;
+        for byte in &rom {
+            global = global.wrapping_add(*byte as u16);
+        }
+        rom[0x014E..0x0150].copy_from_slice(&global.to_be_bytes());
+        rom
+    }
+
+    //for after a test has poked at the header
+    fn fix_header_checksum(rom: &mut [u8]) {
+        let mut checksum: u8 = 0;
+        for address in 0x0134..=0x014C {
+            checksum = checksum.wrapping_sub(rom[address]).wrapping_sub(1);
+        }
+        rom[0x014D] = checksum;
+    }
+
+    //somewhere to put a rom and its .sav that no other test is using
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gb-cart-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sav_round_trip() {
+        let dir = scratch_dir("sav");
+        let rom_path = dir.join("game.gb");
+        //MBC3+TIMER+RAM+BATTERY, 32KiB of ram
+        std::fs::write(&rom_path, test_rom(8, 0x10, 0x03)).unwrap();
+
+        let mut cart = Cart::from_file(&rom_path, &LoadOptions::default()).unwrap();
+        cart.write8(0x0000, 0x0A).unwrap();
+        cart.write8(0x4000, 0x02).unwrap();
+        cart.write8(0xA005, 0x42).unwrap();
+        //minutes, so a second ticking over mid test doesnt matter
+        cart.write8(0x4000, 0x09).unwrap();
+        cart.write8(0xA000, 17).unwrap();
+        cart.flush_save();
+
+        let save = std::fs::read(dir.join("game.sav")).unwrap();
+        assert_eq!(save.len(), 0x8000 + Rtc::FOOTER_LEN);
+        assert_eq!(save[2 * 0x2000 + 5], 0x42);
+
+        let mut cart = Cart::from_file(&rom_path, &LoadOptions::default()).unwrap();
+        cart.write8(0x0000, 0x0A).unwrap();
+        cart.write8(0x4000, 0x02).unwrap();
+        assert_eq!(cart.read8(0xA005).unwrap(), 0x42);
+        cart.write8(0x6000, 0x00).unwrap();
+        cart.write8(0x6000, 0x01).unwrap();
+        cart.write8(0x4000, 0x09).unwrap();
+        assert_eq!(cart.read8(0xA000).unwrap(), 17);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn no_battery_no_save() {
+        let dir = scratch_dir("nobattery");
+        let rom_path = dir.join("game.gb");
+        //MBC1+RAM, no battery
+        std::fs::write(&rom_path, test_rom(4, 0x02, 0x02)).unwrap();
+
+        let mut cart = Cart::from_file(&rom_path, &LoadOptions::default()).unwrap();
+        cart.write8(0x0000, 0x0A).unwrap();
+        cart.write8(0xA000, 0x42).unwrap();
+        cart.flush_save();
+        assert!(!dir.join("game.sav").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn parse_header(rom: &mut [u8]) -> CartHeader {
+        fix_header_checksum(rom);
+        CartHeader::new(rom[0x0100..0x0150].try_into().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn cart_type_decoding() {
+        let cart_type = CartType::from(0x10);
+        assert_eq!(cart_type.mapper, MapperKind::Mbc3);
+        assert!(cart_type.timer && cart_type.ram && cart_type.battery);
+        assert_eq!(cart_type.to_string(), "MBC3+TIMER+RAM+BATTERY");
+
+        assert_eq!(CartType::from(0x00).to_string(), "ROM ONLY");
+        assert_eq!(CartType::from(0x1E).to_string(), "MBC5+RUMBLE+RAM+BATTERY");
+        assert_eq!(
+            CartType::from(0x22).to_string(),
+            "MBC7+SENSOR+RUMBLE+RAM+BATTERY"
+        );
+        assert_eq!(CartType::from(0x42).mapper, MapperKind::Unknown(0x42));
+    }
+
+    #[test]
+    fn header_field_decoding() {
+        assert_eq!(CgbSupport::from(0x00), CgbSupport::DmgOnly);
+        assert_eq!(CgbSupport::from(0x80), CgbSupport::Compatible);
+        assert_eq!(CgbSupport::from(0xC0), CgbSupport::CgbOnly);
+        assert_eq!(Destination::from(0x00), Destination::Japan);
+        assert_eq!(Destination::from(0x01), Destination::Overseas);
+        assert_eq!(Destination::from(0x07), Destination::Unknown(0x07));
+
+        let mut rom = test_rom(2, 0x00, 0x00);
+        rom[0x0134..0x0144].copy_from_slice(b"POKEMON_SLVAAXE\x80");
+        rom[0x0144..0x0146].copy_from_slice(b"01");
+        rom[0x0146] = 0x03;
+        rom[0x0148] = 0x52;
+        rom[0x0149] = 0x05;
+        rom[0x014A] = 0x01;
+        rom[0x014B] = 0x33;
+        let header = parse_header(&mut rom);
+        assert_eq!(header.title, "POKEMON_SLV");
+        assert_eq!(header.man_code.as_deref(), Some("AAXE"));
+        assert_eq!(header.cgb, CgbSupport::Compatible);
+        assert!(header.sgb);
+        assert_eq!(header.rom_size, 72 * 0x4000);
+        assert_eq!(header.ram_size, 0x10000);
+        assert_eq!(header.destination, Destination::Overseas);
+        assert_eq!(header.licensee, Licensee::New(*b"01"));
+        assert_eq!(header.licensee.publisher(), Some("Nintendo R&D1"));
+
+        //no SGB without the new licensee code, and a DMG cart gets all 16 title characters
+        rom[0x0134..0x0144].copy_from_slice(b"SIXTEEN CHARSXYZ");
+        rom[0x014B] = 0x01;
+        let header = parse_header(&mut rom);
+        assert_eq!(header.title, "SIXTEEN CHARSXYZ");
+        assert_eq!(header.man_code, None);
+        assert!(!header.sgb);
+        assert_eq!(header.licensee, Licensee::Old(0x01));
+    }
+
+    #[test]
+    fn bad_sizes_fail_to_load() {
+        let mut rom = test_rom(2, 0x00, 0x00);
+        rom[0x0148] = 0x42;
+        fix_header_checksum(&mut rom);
+        assert!(CartHeader::new(rom[0x0100..0x0150].try_into().unwrap()).is_err());
+
+        //header says 4 banks, file has 2
+        let mut rom = test_rom(2, 0x01, 0x00);
+        rom[0x0148] = 0x01;
+        fix_header_checksum(&mut rom);
+        assert!(Cart::new(&mut std::io::Cursor::new(rom), &LoadOptions::default()).is_err());
+
+        assert!(Cart::new(
+            &mut std::io::Cursor::new(vec![0; 0x100]),
+            &LoadOptions::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn bad_header_checksum_needs_lenient() {
+        let mut rom = test_rom(2, 0x00, 0x00);
+        rom[0x014D] = rom[0x014D].wrapping_add(1);
+        let result = Cart::new(
+            &mut std::io::Cursor::new(rom.clone()),
+            &LoadOptions::default(),
+        );
+        assert!(result.is_err());
+
+        let cart = Cart::new(
+            &mut std::io::Cursor::new(rom),
+            &LoadOptions { lenient: true },
+        )
+        .unwrap();
+        assert!(cart.fails_boot_checks());
+        assert!(matches!(
+            cart.warnings()[0],
+            HeaderWarning::HeaderChecksum { .. }
+        ));
+    }
+
+    #[test]
+    fn bad_logo_needs_lenient() {
+        let mut rom = test_rom(2, 0x00, 0x00);
+        rom[0x0104] = 0x00;
+        assert!(Cart::new(
+            &mut std::io::Cursor::new(rom.clone()),
+            &LoadOptions::default()
+        )
+        .is_err());
+        let cart = Cart::new(
+            &mut std::io::Cursor::new(rom),
+            &LoadOptions { lenient: true },
+        )
+        .unwrap();
+        assert!(cart.fails_boot_checks());
+        assert!(cart.warnings().contains(&HeaderWarning::LogoMismatch));
     }
 
-    pub fn write(&mut self, address: u16, data: &[u8]) -> Result<usize, ExecutionError> {
-        warn!("writing to cart is just going to nothingness rn");
-        return Ok(data.len());
+    #[test]
+    fn bad_global_checksum_only_warns() {
+        let mut rom = test_rom(2, 0x00, 0x00);
+        rom[0x014E] ^= 0xFF;
+        let cart = Cart::new(&mut std::io::Cursor::new(rom), &LoadOptions::default()).unwrap();
+        assert!(!cart.fails_boot_checks());
+        assert_eq!(cart.warnings().len(), 1);
+        assert!(matches!(
+            cart.warnings()[0],
+            HeaderWarning::GlobalChecksum { .. }
+        ));
     }
 }
@@ -7,12 +7,21 @@ use Register8::*;
 #[derive(Clone)]
 pub struct Cpu {
     pub rf: RegisterFile,
+    //interrupt master enable, set by EI/RETI and cleared by DI
+    pub ime: bool,
+    //EI doesnt take effect until after the instruction following it, this is set in between
+    pub ime_pending: bool,
+    //set when HALT is executed with IME off and an interrupt already pending, see System::step
+    pub halt_bug: bool,
 }
 
 impl Cpu {
     pub fn new() -> Result<Self, std::io::Error> {
         Ok(Cpu {
             rf: RegisterFile::new(),
+            ime: false,
+            ime_pending: false,
+            halt_bug: false,
         })
     }
 }
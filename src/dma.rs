@@ -0,0 +1,79 @@
+/*OAM DMA
+$FF46   DMA   OAM DMA source address & start   R/W   All
+
+writing XX here copies XX00-XX9F into OAM (FE00-FE9F), one byte per M cycle for 160 M cycles, starting 1 M cycle
+after the write. the DMG has two buses, the external one (cart rom/ram, wram and echo) and the one vram sits on.
+while a transfer runs it owns whichever one it is reading from, plus OAM, and the cpu gets 0xFF back from those
+(writes are dropped). the other bus, IO and HRAM still work, but games cant count on which bus is free, so they
+copy a tiny wait loop into HRAM and run the DMA from there.
+
+writing FF46 again mid transfer starts over from the new source. the old transfer keeps going (and keeps the bus
+locked) during the startup cycle of the new one, so there is no gap where the cpu gets the bus back*/
+
+#[derive(Debug, Default)]
+pub struct Dma {
+    source: u16,
+    //next byte to copy, 0-159
+    index: u16,
+    active: bool,
+    //source of a transfer that was just requested and is in its startup cycle
+    pending: Option<u16>,
+}
+
+pub const OAM_DMA_LEN: u16 = 160;
+
+impl Dma {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn start(&mut self, value: u8) {
+        //DMG cant read from OAM or IO, sources above DF just land in echo ram
+        let mut source = (value as u16) << 8;
+        if source >= 0xE000 {
+            source -= 0x2000;
+        }
+        self.pending = Some(source);
+    }
+
+    //whether the cpu is locked out of this address right now
+    pub fn blocks(&self, address: u16) -> bool {
+        if !self.active {
+            return false;
+        }
+        let source_on_vram_bus = (0x8000..=0x9FFF).contains(&self.source);
+        match address {
+            //where the bytes are going
+            0xFE00..=0xFEFF => true,
+            0x8000..=0x9FFF => source_on_vram_bus,
+            //IO (FF46 included, so a transfer can be restarted) and HRAM are inside the cpu
+            0xFF00..=0xFFFF => false,
+            _ => !source_on_vram_bus,
+        }
+    }
+
+    //runs one M cycle. hands back the (source address, oam offset) of the byte to copy this cycle, if there is one
+    pub fn step(&mut self) -> Option<(u16, usize)> {
+        let copy = if self.active {
+            let copy = (self.source + self.index, self.index as usize);
+            self.index += 1;
+            if self.index == OAM_DMA_LEN {
+                self.active = false;
+            }
+            Some(copy)
+        } else {
+            None
+        };
+
+        //a new transfer takes over at the end of its startup cycle, so the first byte goes next cycle
+        if let Some(source) = self.pending.take() {
+            self.source = source;
+            self.index = 0;
+            self.active = true;
+        }
+
+        copy
+    }
+}
@@ -0,0 +1,157 @@
+/*
+$FF0F	IF	Interrupt flag	    R/W	All
+$FFFF	IE	Interrupt enable	R/W	All
+
+Bit 4 - Joypad   (vector $60)
+Bit 3 - Serial   (vector $58)
+Bit 2 - Timer    (vector $50)
+Bit 1 - LCD STAT (vector $48)
+Bit 0 - VBlank   (vector $40)
+
+lower bits have higher priority, so if more than one is pending we always service the lowest one first */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    //in priority order, highest first
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub fn bit(self) -> u8 {
+        match self {
+            Interrupt::VBlank => 0b0000_0001,
+            Interrupt::Stat => 0b0000_0010,
+            Interrupt::Timer => 0b0000_0100,
+            Interrupt::Serial => 0b0000_1000,
+            Interrupt::Joypad => 0b0001_0000,
+        }
+    }
+
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::Stat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60,
+        }
+    }
+}
+
+impl std::fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interrupt::VBlank => write!(f, "VBlank"),
+            Interrupt::Stat => write!(f, "STAT"),
+            Interrupt::Timer => write!(f, "Timer"),
+            Interrupt::Serial => write!(f, "Serial"),
+            Interrupt::Joypad => write!(f, "Joypad"),
+        }
+    }
+}
+
+//holds IE and IF. every unit that can raise an interrupt (ppu, timer, serial, joypad) gets handed a &mut to this
+//when it is ticked and calls request() on it, the cpu side then checks pending() between instructions
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InterruptController {
+    pub IE: u8,
+    pub IF: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn request(&mut self, int: Interrupt) {
+        self.IF |= int.bit();
+    }
+
+    pub fn acknowledge(&mut self, int: Interrupt) {
+        self.IF &= !int.bit();
+    }
+
+    //interrupts that are both requested and enabled. this is what wakes up HALT regardless of IME
+    pub fn pending(&self) -> u8 {
+        self.IE & self.IF & 0b0001_1111
+    }
+
+    pub fn highest_pending(&self) -> Option<Interrupt> {
+        let pending = self.pending();
+        Interrupt::ALL
+            .into_iter()
+            .find(|int| (pending & int.bit()) != 0)
+    }
+
+    //the top 3 bits of IF arent wired to anything and always read back as 1
+    pub fn read_if(&self) -> u8 {
+        self.IF | 0b1110_0000
+    }
+
+    pub fn write_if(&mut self, value: u8) {
+        self.IF = value & 0b0001_1111;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_masks() {
+        let mut interrupts = InterruptController::new();
+        interrupts.write_if(0xFF);
+        assert_eq!(interrupts.IF, 0x1F);
+        assert_eq!(interrupts.read_if(), 0xFF);
+        interrupts.write_if(0x00);
+        assert_eq!(interrupts.read_if(), 0xE0);
+    }
+
+    #[test]
+    fn pending_needs_both_enable_and_request() {
+        let mut interrupts = InterruptController::new();
+        interrupts.IE = 0xFF;
+        assert_eq!(interrupts.pending(), 0);
+        interrupts.IF = 0xFF;
+        //nothing above joypad can ever be pending, whatever IE and IF hold
+        assert_eq!(interrupts.pending(), 0x1F);
+        interrupts.IE = Interrupt::Timer.bit();
+        assert_eq!(interrupts.pending(), Interrupt::Timer.bit());
+    }
+
+    #[test]
+    fn priority_order() {
+        let mut interrupts = InterruptController::new();
+        interrupts.IE = 0xFF;
+        interrupts.request(Interrupt::Joypad);
+        interrupts.request(Interrupt::Timer);
+        interrupts.request(Interrupt::Stat);
+        for expected in [Interrupt::Stat, Interrupt::Timer, Interrupt::Joypad] {
+            let int = interrupts.highest_pending().unwrap();
+            assert_eq!(int, expected);
+            interrupts.acknowledge(int);
+        }
+        assert_eq!(interrupts.highest_pending(), None);
+
+        //a higher priority request that isnt enabled doesnt get in the way
+        interrupts.request(Interrupt::VBlank);
+        interrupts.request(Interrupt::Serial);
+        interrupts.IE = Interrupt::Serial.bit();
+        assert_eq!(interrupts.highest_pending(), Some(Interrupt::Serial));
+    }
+}
@@ -1,12 +1,18 @@
 use crate::audio::Audio;
+use crate::bus::Bus;
+use crate::interrupt::{Interrupt, InterruptController};
+use crate::joypad::Joypad;
 use crate::ppu::Ppu;
+use crate::serial::Serial;
 use crate::system::ExecutionError;
+use crate::timer::Timer;
 //memory mapped registers/ other assorted IO
 
 /*IO memory map
 $FF00		    DMG	Joypad input
 $FF01	$FF02	DMG	Serial transfer
 $FF04	$FF07	DMG	Timer and divider
+$FF0F		    DMG	Interrupt flag (IF)
 $FF10	$FF26	DMG	Audio
 $FF30	$FF3F	DMG	Wave pattern
 $FF40	$FF4B	DMG	LCD Control, Status, Position, Scrolling, and Palettes
@@ -17,11 +23,14 @@ $FF68	$FF69	CGB	BG / OBJ Palettes
 $FF70		    CGB	WRAM Bank Select */
 #[derive(Default)]
 pub struct Io {
-    //joypad: u8,
+    pub joypad: Joypad,
     pub bootrom_disable: u8,
     pub audio: Audio,
     pub ppu: Ppu,
-    //dmg_serial_transfer: [u8;2]
+    pub timer: Timer,
+    pub serial: Serial,
+    //IF lives here at 0xFF0F, IE is at 0xFFFF but we keep it in the same place so everything can see both
+    pub interrupts: InterruptController,
 }
 
 impl Io {
@@ -30,72 +39,65 @@ impl Io {
             ..Default::default()
         }
     }
+}
 
-    pub fn read(&mut self, address: u16, len: usize) -> Result<Vec<u8>, ExecutionError> {
+impl Bus for Io {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
         match address {
-            0xFF00 => unimplemented!("tried to read joypad input"),
-            0xFF01..=0xFF02 => unimplemented!("tried to read DMG serial transfer"),
-            0xFF04..=0xFF07 => unimplemented!("tried to read DMG timer and divider"),
-            0xFF10..=0xFF26 => {
-                //unimplemented!("tried to read DMG audio")
-                self.audio.read(address, len)
-            }
-            0xFF30..=0xFF3F => unimplemented!("tried to read DMG wave pattern"),
+            0xFF00 => Ok(self.joypad.read()),
+            0xFF01..=0xFF02 => self.serial.read8(address),
+            0xFF04..=0xFF07 => self.timer.read8(address),
+            0xFF0F => Ok(self.interrupts.read_if()),
+            0xFF10..=0xFF3F => self.audio.read8(address),
             0xFF40..=0xFF4B => {
                 //unimplemented!("tried to read LCD control stuff")
-                let mut ret_vec: Vec<u8> = Vec::new();
-                for i in address as usize..address as usize + len {
-                    ret_vec.push(self.ppu.read(i.try_into().unwrap())?);
-                }
-                Ok(ret_vec)
+                self.ppu.read8(address)
             }
-            0xFF4F => unimplemented!("tried to read CGB VRAM bank select"),
-            0xFF50 => {
-                if len > 1 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
-                }
-                Ok(vec![self.bootrom_disable])
+            0xFF50 => Ok(self.bootrom_disable),
+            //the CGB only registers (VRAM bank, VRAM DMA, palettes, WRAM bank) dont exist on DMG, and neither does
+            //anything else in the gaps. nothing drives the bus so it reads back all 1s
+            _ => {
+                trace!("read from unmapped I/O address {:#06X}", address);
+                Ok(0xFF)
             }
-            0xFF51..=0xFF55 => unimplemented!("tried to read CGB VRAM DMA"),
-            0xFF68..=0xFF69 => unimplemented!("tried to read CGB BG/OBJ PPalettes"),
-            0xFF70 => unimplemented!("tried to read CGB WRAM bank select"),
-            _ => panic!("read from bad address in I/O range"),
         }
     }
 
-    pub fn write(&mut self, address: u16, data: &[u8]) -> Result<usize, ExecutionError> {
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
         match address {
-            0xFF00 => unimplemented!("tried to write joypad input"),
-            0xFF01..=0xFF02 => unimplemented!("tried to write DMG serial transfer"),
-            0xFF04..=0xFF07 => unimplemented!("tried to write DMG timer and divider"),
-            0xFF10..=0xFF26 => {
-                //unimplemented!("tried to read DMG audio")
-                self.audio.write(address, data)
+            0xFF00 => {
+                self.joypad.write(value, &mut self.interrupts);
+                Ok(())
             }
-            0xFF30..=0xFF3F => unimplemented!("tried to write DMG wave pattern"),
+            0xFF01..=0xFF02 => self.serial.write8(address, value),
+            0xFF04..=0xFF07 => self.timer.write8(address, value),
+            0xFF0F => {
+                self.interrupts.write_if(value);
+                Ok(())
+            }
+            0xFF10..=0xFF3F => self.audio.write8(address, value),
             0xFF40..=0xFF4B => {
                 //unimplemented!("tried to write LCD control stuff")
-
-                for (i, v) in data.iter().enumerate() {
-                    self.ppu
-                        .write(((address as usize) + i).try_into().unwrap(), *v)
-                        .unwrap();
+                self.ppu.write8(address, value)?;
+                //STAT writes can raise an interrupt on DMG
+                if self.ppu.take_stat_write_glitch() {
+                    self.interrupts.request(Interrupt::Stat);
                 }
-                Ok(data.len())
+                Ok(())
             }
-            0xFF4F => unimplemented!("tried to write CGB VRAM bank select"),
             0xFF50 => {
-                if data.len() > 1 {
-                    Err(ExecutionError::IllegalWrite(address as usize))
-                } else {
-                    self.bootrom_disable = data[0];
-                    return Ok(1);
-                }
+                self.bootrom_disable = value;
+                Ok(())
+            }
+            //same as reads, plenty of games poke at registers that arent there (tetris writes 0xFF7F)
+            _ => {
+                trace!(
+                    "write of {:#04X} to unmapped I/O address {:#06X}",
+                    value,
+                    address
+                );
+                Ok(())
             }
-            0xFF51..=0xFF55 => unimplemented!("tried to write CGB VRAM DMA"),
-            0xFF68..=0xFF69 => unimplemented!("tried to write CGB BG/OBJ PPalettes"),
-            0xFF70 => unimplemented!("tried to write CGB WRAM bank select"),
-            _ => panic!("write from bad address in I/O range"),
         }
     }
 }
@@ -0,0 +1,197 @@
+use crate::interrupt::{Interrupt, InterruptController};
+
+/*$FF00	P1/JOYP	Joypad	Mixed	All
+
+Bit 7-6 Not used, read as 1
+Bit 5 - P15 Select Action buttons    (0=Select)
+Bit 4 - P14 Select Direction buttons (0=Select)
+Bit 3 - P13 Input: Down  or Start    (0=Pressed) (Read Only)
+Bit 2 - P12 Input: Up    or Select   (0=Pressed) (Read Only)
+Bit 1 - P11 Input: Left  or B        (0=Pressed) (Read Only)
+Bit 0 - P10 Input: Right or A        (0=Pressed) (Read Only)
+
+the buttons are a 2x4 matrix, the game pulls one (or both) of the select lines low and reads back whichever buttons
+in that group are held as 0s. the joypad interrupt fires whenever one of the 4 input lines goes from high to low,
+which is also the only thing that gets us out of STOP*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Right,
+        Button::Left,
+        Button::Up,
+        Button::Down,
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+    ];
+
+    //which bit in our pressed byte this is. low nibble is directions, high nibble is actions, and each nibble is
+    //in the same order as the input lines
+    fn bit(self) -> u8 {
+        match self {
+            Button::Right => 0b0000_0001,
+            Button::Left => 0b0000_0010,
+            Button::Up => 0b0000_0100,
+            Button::Down => 0b0000_1000,
+            Button::A => 0b0001_0000,
+            Button::B => 0b0010_0000,
+            Button::Select => 0b0100_0000,
+            Button::Start => 0b1000_0000,
+        }
+    }
+}
+
+impl std::fmt::Display for Button {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Button::Right => write!(f, "Right"),
+            Button::Left => write!(f, "Left"),
+            Button::Up => write!(f, "Up"),
+            Button::Down => write!(f, "Down"),
+            Button::A => write!(f, "A"),
+            Button::B => write!(f, "B"),
+            Button::Select => write!(f, "Select"),
+            Button::Start => write!(f, "Start"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Joypad {
+    //bits 4 and 5 as last written, 0 means that group is selected
+    select: u8,
+    //1 = held, see Button::bit
+    pressed: u8,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self {
+            select: 0b0011_0000,
+            pressed: 0,
+        }
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    //the 4 input lines, active low
+    pub fn lines(&self) -> u8 {
+        let mut lines = 0b1111;
+        if (self.select & 0b0001_0000) == 0 {
+            lines &= !(self.pressed & 0x0F);
+        }
+        if (self.select & 0b0010_0000) == 0 {
+            lines &= !(self.pressed >> 4);
+        }
+        lines
+    }
+
+    pub fn read(&self) -> u8 {
+        0b1100_0000 | self.select | self.lines()
+    }
+
+    //changing the select lines can pull an input line low just as well as pressing a button can
+    pub fn write(&mut self, value: u8, interrupts: &mut InterruptController) {
+        let before = self.lines();
+        self.select = value & 0b0011_0000;
+        self.check_falling(before, interrupts);
+    }
+
+    pub fn set_button(
+        &mut self,
+        button: Button,
+        pressed: bool,
+        interrupts: &mut InterruptController,
+    ) {
+        let before = self.lines();
+        if pressed {
+            self.pressed |= button.bit();
+        } else {
+            self.pressed &= !button.bit();
+        }
+        self.check_falling(before, interrupts);
+    }
+
+    fn check_falling(&self, before: u8, interrupts: &mut InterruptController) {
+        if (before & !self.lines()) != 0 {
+            interrupts.request(Interrupt::Joypad);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_selected_reads_high() {
+        let mut joypad = Joypad::new();
+        let mut interrupts = InterruptController::new();
+        joypad.set_button(Button::A, true, &mut interrupts);
+        joypad.set_button(Button::Down, true, &mut interrupts);
+        assert_eq!(joypad.read(), 0xFF);
+        assert_eq!(interrupts.IF, 0);
+    }
+
+    #[test]
+    fn selected_group_reads_low() {
+        let mut joypad = Joypad::new();
+        let mut interrupts = InterruptController::new();
+        joypad.set_button(Button::Start, true, &mut interrupts);
+        joypad.set_button(Button::Left, true, &mut interrupts);
+
+        joypad.write(0x10, &mut interrupts);
+        assert_eq!(joypad.read(), 0b1101_0111);
+        joypad.write(0x20, &mut interrupts);
+        assert_eq!(joypad.read(), 0b1110_1101);
+        //both groups at once just AND together
+        joypad.write(0x00, &mut interrupts);
+        assert_eq!(joypad.read(), 0b1100_0101);
+    }
+
+    #[test]
+    fn press_requests_interrupt() {
+        let mut joypad = Joypad::new();
+        let mut interrupts = InterruptController::new();
+        joypad.write(0x20, &mut interrupts);
+        joypad.set_button(Button::Up, true, &mut interrupts);
+        assert_eq!(interrupts.IF, Interrupt::Joypad.bit());
+
+        //releasing is a rising edge, and the unselected group isnt connected to anything
+        interrupts.IF = 0;
+        joypad.set_button(Button::Up, false, &mut interrupts);
+        joypad.set_button(Button::B, true, &mut interrupts);
+        assert_eq!(interrupts.IF, 0);
+        joypad.set_button(Button::Right, true, &mut interrupts);
+        assert_eq!(interrupts.IF, Interrupt::Joypad.bit());
+    }
+
+    #[test]
+    fn selecting_a_held_group_requests_interrupt() {
+        let mut joypad = Joypad::new();
+        let mut interrupts = InterruptController::new();
+        joypad.set_button(Button::Select, true, &mut interrupts);
+        assert_eq!(interrupts.IF, 0);
+        joypad.write(0x10, &mut interrupts);
+        assert_eq!(interrupts.IF, Interrupt::Joypad.bit());
+    }
+}
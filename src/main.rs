@@ -1,7 +1,10 @@
 #![feature(bigint_helper_methods)]
 
-use crate::cart::Cart;
+use crate::audio_sink::{AudioSink, LiveSink, WavRecorder};
+use crate::cart::{Cart, LoadOptions};
 use crate::cpu::Cpu;
+use crate::joypad::Button;
+use crate::serial::{Capture, Disconnected, SerialLink, TcpLink};
 use eframe::egui;
 use egui::{ColorImage, TextureOptions};
 use std::{
@@ -18,11 +21,19 @@ extern crate pretty_env_logger;
 extern crate log;
 
 mod audio;
+mod audio_sink;
+mod bus;
 mod cart;
 mod cpu;
+mod dma;
+mod interrupt;
 mod io;
+mod joypad;
+mod mapper;
 mod ppu;
+mod serial;
 mod system;
+mod timer;
 fn main() -> Result<(), eframe::Error> {
     pretty_env_logger::init();
     info!("starting up");
@@ -57,6 +68,110 @@ struct App {
     mem_editor: egui_memory_editor::MemoryEditor,
     dummy_memory: Vec<u8>,
     //memory_rx: Receiver<(usize, Vec<u8>)>,
+    key_bindings: KeyBindings,
+    //what we last told the backend about each button, so we only send changes
+    held_buttons: [bool; 8],
+    //the button waiting for a key press in the controls window, if any
+    rebinding: Option<Button>,
+    //everything the game sent over serial, when we are capturing it
+    serial_output: Option<Arc<Mutex<Vec<u8>>>>,
+    //sound stops when this is dropped
+    _audio_stream: Option<cpal::Stream>,
+}
+
+//--record-audio <path> writes everything to a .wav at a fixed rate, otherwise we play it live if we can.
+//no audio device just means no sound
+fn audio_sink_from_args() -> (Option<Box<dyn AudioSink>>, Option<cpal::Stream>) {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--record-audio")
+        .and_then(|i| args.get(i + 1))
+    {
+        match WavRecorder::create(std::path::Path::new(path), audio::DEFAULT_SAMPLE_RATE) {
+            Ok(recorder) => return (Some(Box::new(recorder)), None),
+            Err(e) => warn!("couldnt create audio recording {path}: {e}"),
+        }
+    }
+    match LiveSink::open() {
+        Some((sink, stream)) => (Some(Box::new(sink)), Some(stream)),
+        None => (None, None),
+    }
+}
+
+//what goes on the other end of the link cable:
+//  --serial-capture          keep whatever the game sends, blargg's test roms print their results this way
+//  --link-host <addr>        wait for another emulator to connect to us
+//  --link-connect <addr>     connect to another emulator
+//and nothing plugged in otherwise
+fn serial_link_from_args() -> (Box<dyn SerialLink>, Option<Arc<Mutex<Vec<u8>>>>) {
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    if let Some(addr) = value_of("--link-host") {
+        match TcpLink::host(addr.as_str()) {
+            Ok(link) => return (Box::new(link), None),
+            Err(e) => warn!("couldnt host a serial link on {addr}: {e}"),
+        }
+    } else if let Some(addr) = value_of("--link-connect") {
+        match TcpLink::connect(addr.as_str()) {
+            Ok(link) => return (Box::new(link), None),
+            Err(e) => warn!("couldnt connect a serial link to {addr}: {e}"),
+        }
+    } else if args.iter().any(|arg| arg == "--serial-capture") {
+        let capture = Capture::new();
+        let output = capture.output();
+        return (Box::new(capture), Some(output));
+    }
+    (Box::new(Disconnected), None)
+}
+
+//which keyboard key drives which joypad button
+struct KeyBindings {
+    keys: [(Button, egui::Key); 8],
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: [
+                (Button::Right, egui::Key::ArrowRight),
+                (Button::Left, egui::Key::ArrowLeft),
+                (Button::Up, egui::Key::ArrowUp),
+                (Button::Down, egui::Key::ArrowDown),
+                (Button::A, egui::Key::X),
+                (Button::B, egui::Key::Z),
+                (Button::Select, egui::Key::Backspace),
+                (Button::Start, egui::Key::Enter),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    //binding a key thats already in use swaps the two, so every button always has exactly one key
+    fn rebind(&mut self, button: Button, key: egui::Key) {
+        let old_key = self.key_for(button);
+        for (b, k) in self.keys.iter_mut() {
+            if *b == button {
+                *k = key;
+            } else if *k == key {
+                *k = old_key;
+            }
+        }
+    }
+
+    fn key_for(&self, button: Button) -> egui::Key {
+        self.keys
+            .iter()
+            .find(|(b, _)| *b == button)
+            .map(|(_, k)| *k)
+            .unwrap()
+    }
 }
 
 impl App {
@@ -73,8 +188,16 @@ impl App {
         //let (mem_tx, mem_rx) = channel();
 
         let cpu = cpu::Cpu::new().unwrap();
-        let cart = Cart::new(&mut std::fs::File::open("./roms/test_rom.gb").unwrap()).unwrap();
-        let io = io::Io::new();
+        //--lenient boots carts with a broken header (bad logo or checksum), mostly for homebrew
+        let load_options = LoadOptions {
+            lenient: std::env::args().any(|arg| arg == "--lenient"),
+        };
+        let cart =
+            Cart::from_file(std::path::Path::new("./roms/test_rom.gb"), &load_options).unwrap();
+        let skip_boot_rom = cart.fails_boot_checks();
+        let mut io = io::Io::new();
+        let (serial_link, serial_output) = serial_link_from_args();
+        io.serial = serial::Serial::new(serial_link);
         let boot_room = include_bytes!("../dmg.bin").to_vec();
         println!("boot room is : {:x} bytes long", boot_room.len());
 
@@ -91,6 +214,15 @@ impl App {
             boot_room.try_into().unwrap(),
             //mem_tx,
         );
+        //the boot rom would just hang on a cart like this, so jump straight into it instead
+        if skip_boot_rom {
+            warn!("cart fails the boot rom checks, skipping the boot rom");
+            sys.skip_boot_rom().unwrap();
+        }
+        let (audio_sink, audio_stream) = audio_sink_from_args();
+        if let Some(sink) = audio_sink {
+            sys.set_audio_sink(sink);
+        }
         let big_ole_mutex = Arc::new(Mutex::new(sys));
         let sys_for_us = big_ole_mutex.clone();
 
@@ -120,6 +252,11 @@ impl App {
             //.with_address_range("ExRAM", 0x)
             dummy_memory: vec![0; 0xFFFF],
             //memory_rx: mem_rx,
+            key_bindings: KeyBindings::default(),
+            held_buttons: [false; 8],
+            rebinding: None,
+            serial_output,
+            _audio_stream: audio_stream,
         }
     }
 }
@@ -156,6 +293,41 @@ impl eframe::App for App {
             self.cpu_state = Some(l.unwrap());
         }
 
+        //input
+        //-----------------------------------------------------------------------------------------
+        if let Some(button) = self.rebinding {
+            //let go of everything while we wait, otherwise a held button would stay stuck down
+            for (i, (held, _)) in self.key_bindings.keys.iter().enumerate() {
+                if self.held_buttons[i] {
+                    self.held_buttons[i] = false;
+                    let _ = self.command_tx.send(BackendCmd::Input(*held, false));
+                }
+            }
+            //grab the next key that goes down and bind it instead of passing it to the game
+            let pressed = ctx.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Key {
+                        key, pressed: true, ..
+                    } => Some(*key),
+                    _ => None,
+                })
+            });
+            if let Some(key) = pressed {
+                self.key_bindings.rebind(button, key);
+                self.rebinding = None;
+            }
+        } else {
+            for (i, (button, key)) in self.key_bindings.keys.iter().enumerate() {
+                let down = ctx.input(|input| input.key_down(*key));
+                if down != self.held_buttons[i] {
+                    self.held_buttons[i] = down;
+                    //if the backend is gone theres nobody to tell
+                    let _ = self.command_tx.send(BackendCmd::Input(*button, down));
+                }
+            }
+        }
+        //-----------------------------------------------------------------------------------------
+
         //update all of our memory views
         /*let mut sys = self.system_mutex.lock().unwrap();
         unsafe {
@@ -207,10 +379,13 @@ impl eframe::App for App {
         //-----------------------------------------------------------------------------------------
         egui::SidePanel::right("cpu").show(ctx, |ui| {
             ui.heading("cpu state");
-            ui.add_sized(
-                [ui.available_width(), 10.0],
-                egui::widgets::Label::new(format!("{}", self.cpu_state.clone().unwrap())),
-            );
+            //the core only sends this once a frame, so there might not be one yet
+            if let Some(cpu) = &self.cpu_state {
+                ui.add_sized(
+                    [ui.available_width(), 10.0],
+                    egui::widgets::Label::new(format!("{}", cpu)),
+                );
+            }
         });
 
         //screen area
@@ -239,6 +414,42 @@ impl eframe::App for App {
         });
         //-----------------------------------------------------------------------------------------
 
+        //controls
+        //-----------------------------------------------------------------------------------------
+        egui::Window::new("controls").show(ctx, |ui| {
+            egui::Grid::new("key_bindings").show(ui, |ui| {
+                for button in Button::ALL {
+                    ui.label(format!("{}", button));
+                    let text = if self.rebinding == Some(button) {
+                        "press a key...".to_string()
+                    } else {
+                        format!("{:?}", self.key_bindings.key_for(button))
+                    };
+                    if ui.button(text).clicked() {
+                        self.rebinding = Some(button);
+                    }
+                    ui.end_row();
+                }
+            });
+            if ui.button("reset to defaults").clicked() {
+                self.key_bindings = KeyBindings::default();
+                self.rebinding = None;
+            }
+        });
+        //-----------------------------------------------------------------------------------------
+
+        //serial output
+        //-----------------------------------------------------------------------------------------
+        if let Some(output) = &self.serial_output {
+            let text = String::from_utf8_lossy(&output.lock().unwrap()).into_owned();
+            egui::Window::new("serial").show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.monospace(text);
+                });
+            });
+        }
+        //-----------------------------------------------------------------------------------------
+
         //memory_editor
         //egui::Window::new("test").show(ctx, add_contents)
         /*egui::Window::new("mem_edit_test").show(ctx, |ui| {
@@ -270,6 +481,14 @@ impl eframe::App for App {
         if let Some(handle) = self.system_handle.take() {
             handle.join().expect("failed to join system thread");
         }
+        //the core thread is gone now, so this is the last chance to get the save onto disk.
+        //still save if it crashed and poisoned the lock, the ram is probably fine
+        let mut sys = match self.system_mutex.lock() {
+            Ok(sys) => sys,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        sys.flush_save();
+        sys.finish_audio();
     }
 }
 
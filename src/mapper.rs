@@ -0,0 +1,991 @@
+use crate::cart::{CartType, MapperKind};
+
+//the memory bank controller on the cart. it owns the rom and the external ram, and decides what shows up at
+//0000-7FFF and A000-BFFF. writes into rom space never touch the rom, they go to the mapper's registers instead
+pub trait Mapper: Send {
+    //0000-7FFF
+    fn read_rom(&self, address: u16) -> u8;
+    //writes to 0000-7FFF
+    fn write_register(&mut self, address: u16, value: u8);
+    //A000-BFFF
+    fn read_ram(&self, address: u16) -> u8;
+    fn write_ram(&mut self, address: u16, value: u8);
+
+    //the whole external ram, for reading and writing .sav files
+    fn ram(&self) -> &[u8];
+    fn ram_mut(&mut self) -> &mut [u8];
+    //only MBC3 carts with the timer have one of these
+    fn rtc(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+}
+
+//picks the mapper out of the header cart type. anything we dont know how to bank is a load error, rather
+//than running it as rom only and having it read garbage the first time it switches banks
+pub fn new_mapper(
+    cart_type: &CartType,
+    rom: Vec<u8>,
+    ram_size: usize,
+) -> Result<Box<dyn Mapper>, std::io::Error> {
+    let mem = CartMemory {
+        rom,
+        ram: vec![0; ram_size],
+    };
+
+    let mapper: Box<dyn Mapper> = match cart_type.mapper {
+        MapperKind::RomOnly => Box::new(RomOnly { mem }),
+        MapperKind::Mbc1 => Box::new(Mbc1::new(mem)),
+        MapperKind::Mbc2 => Box::new(Mbc2::new(mem)),
+        MapperKind::Mbc3 => Box::new(Mbc3::new(mem, cart_type.timer)),
+        MapperKind::Mbc5 => Box::new(Mbc5::new(mem, cart_type.rumble)),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("unsupported cartridge type {}", cart_type),
+            ))
+        }
+    };
+    Ok(mapper)
+}
+
+//rom and external ram, plus the bank math every mapper needs
+struct CartMemory {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl CartMemory {
+    fn rom_bank_count(&self) -> usize {
+        //anything smaller than 2 banks is a broken dump, but dont divide by zero over it
+        (self.rom.len() / 0x4000).max(2)
+    }
+
+    fn ram_bank_count(&self) -> usize {
+        (self.ram.len() / 0x2000).max(1)
+    }
+
+    //reads out of a 16KiB rom bank, wrapping the bank number the same way the unconnected address lines would
+    fn read_rom_bank(&self, bank: usize, address: u16) -> u8 {
+        let bank = bank % self.rom_bank_count();
+        let offset = bank * 0x4000 + (address as usize & 0x3FFF);
+        //the last bank of an undersized dump might not be all there
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    //ram offset for an 8KiB bank, or None if this cart has no ram to speak of
+    fn ram_offset(&self, bank: usize, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let bank = bank % self.ram_bank_count();
+        //carts with 2KiB of ram just mirror it across the whole window
+        Some((bank * 0x2000 + (address as usize & 0x1FFF)) % self.ram.len())
+    }
+
+    //disabled (or missing) ram floats high
+    fn read_ram_bank(&self, enabled: bool, bank: usize, address: u16) -> u8 {
+        match self.ram_offset(bank, address) {
+            Some(offset) if enabled => self.ram[offset],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram_bank(&mut self, enabled: bool, bank: usize, address: u16, value: u8) {
+        if let Some(offset) = self.ram_offset(bank, address) {
+            if enabled {
+                self.ram[offset] = value;
+            }
+        }
+    }
+}
+
+//no mapper at all, 32KiB of rom straight on the bus and maybe 8KiB of ram
+struct RomOnly {
+    mem: CartMemory,
+}
+
+impl Mapper for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.mem.rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    //no mapper to write to, so writes into rom just vanish
+    fn write_register(&mut self, _address: u16, _value: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.mem.read_ram_bank(true, 0, address)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        self.mem.write_ram_bank(true, 0, address, value)
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.mem.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.mem.ram
+    }
+}
+
+/*MBC1
+0000-1FFF   RAM enable (write 0xA in the low nibble to enable, anything else disables)
+2000-3FFF   ROM bank number, low 5 bits. 0 gets bumped to 1
+4000-5FFF   RAM bank number, or upper 2 bits of the ROM bank number
+6000-7FFF   banking mode select
+            0 = simple: 0000-3FFF is always bank 0, ram is always bank 0
+            1 = advanced: the 2 bit register also applies to 0000-3FFF and the ram bank
+
+MBC1M multicarts (the 1MiB collection carts) wire the 2 bit register one line lower, so it selects
+between 4 games of 16 banks each instead of 32 banks each*/
+struct Mbc1 {
+    mem: CartMemory,
+    ram_enable: bool,
+    rom_bank: u8,
+    bank2: u8,
+    advanced_mode: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    fn new(mem: CartMemory) -> Self {
+        let multicart = Self::is_multicart(&mem.rom);
+        Self {
+            mem,
+            ram_enable: false,
+            rom_bank: 1,
+            bank2: 0,
+            advanced_mode: false,
+            multicart,
+        }
+    }
+
+    //there is nothing in the header that says this is a multicart, but each game in one has its own copy of the
+    //nintendo logo at the start of its 256KiB chunk, so look for a second one at bank 0x10
+    fn is_multicart(rom: &[u8]) -> bool {
+        if rom.len() != 0x100000 {
+            return false;
+        }
+        let logo = &rom[0x0104..0x0134];
+        let second_logo = &rom[0x10 * 0x4000 + 0x0104..0x10 * 0x4000 + 0x0134];
+        logo == second_logo
+    }
+
+    //how far over to shift bank2 when building a full bank number
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    //bank mapped into 0000-3FFF
+    fn low_rom_bank(&self) -> usize {
+        if self.advanced_mode {
+            (self.bank2 << self.bank2_shift()) as usize
+        } else {
+            0
+        }
+    }
+
+    //bank mapped into 4000-7FFF
+    fn high_rom_bank(&self) -> usize {
+        //the 0->1 bump only looks at the full 5 bit register, even on multicarts where bit 4 isnt wired
+        let low_bits = if self.multicart {
+            self.rom_bank & 0x0F
+        } else {
+            self.rom_bank
+        };
+        ((self.bank2 << self.bank2_shift()) | low_bits) as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.advanced_mode {
+            self.bank2 as usize
+        } else {
+            0
+        }
+    }
+}
+
+impl Mapper for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.mem.read_rom_bank(self.low_rom_bank(), address),
+            _ => self.mem.read_rom_bank(self.high_rom_bank(), address),
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enable = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = value & 0b0001_1111;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = value & 0b0000_0011,
+            0x6000..=0x7FFF => self.advanced_mode = (value & 0b0000_0001) != 0,
+            _ => unreachable!("MBC1 register write outside of 0000-7FFF"),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.mem
+            .read_ram_bank(self.ram_enable, self.ram_bank(), address)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        let bank = self.ram_bank();
+        self.mem
+            .write_ram_bank(self.ram_enable, bank, address, value)
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.mem.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.mem.ram
+    }
+}
+
+/*MBC2
+0000-3FFF   RAM enable and ROM bank number, bit 8 of the address picks which one
+            bit 8 clear = RAM enable (0xA in the low nibble enables)
+            bit 8 set   = ROM bank number, low 4 bits. 0 gets bumped to 1
+A000-A1FF   512 x 4 bit ram built into the mapper itself, the header ram size is 0 for these
+A200-BFFF   echoes of A000-A1FF
+
+only the low nibble of each ram byte exists, the top half reads back as whatever the bus floats to (1s)*/
+struct Mbc2 {
+    mem: CartMemory,
+    ram_enable: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    fn new(mut mem: CartMemory) -> Self {
+        mem.ram = vec![0; 512];
+        Self {
+            mem,
+            ram_enable: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.mem.read_rom_bank(0, address),
+            _ => self.mem.read_rom_bank(self.rom_bank as usize, address),
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x3FFF => {
+                if (address & 0x0100) == 0 {
+                    self.ram_enable = (value & 0x0F) == 0x0A;
+                } else {
+                    self.rom_bank = value & 0x0F;
+                    if self.rom_bank == 0 {
+                        self.rom_bank = 1;
+                    }
+                }
+            }
+            //nothing up here on an MBC2
+            0x4000..=0x7FFF => {}
+            _ => unreachable!("MBC2 register write outside of 0000-7FFF"),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF;
+        }
+        self.mem.ram[(address & 0x01FF) as usize] | 0xF0
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ram_enable {
+            self.mem.ram[(address & 0x01FF) as usize] = value & 0x0F;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.mem.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.mem.ram
+    }
+}
+
+/*MBC3
+0000-1FFF   RAM and RTC enable (0xA in the low nibble enables)
+2000-3FFF   ROM bank number, 7 bits. 0 gets bumped to 1
+4000-5FFF   00-03 selects a RAM bank for A000-BFFF, 08-0C selects an RTC register instead
+6000-7FFF   latch clock data, writing 00 then 01 copies the live clock into the latched registers*/
+struct Mbc3 {
+    mem: CartMemory,
+    ram_enable: bool,
+    rom_bank: u8,
+    //either a ram bank (0-3) or an rtc register (8-C)
+    ram_rtc_select: u8,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    fn new(mem: CartMemory, has_rtc: bool) -> Self {
+        Self {
+            mem,
+            ram_enable: false,
+            rom_bank: 1,
+            ram_rtc_select: 0,
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+        }
+    }
+
+    //Some(register) if A000-BFFF is currently pointing at the clock instead of ram
+    fn selected_rtc_register(&self) -> Option<u8> {
+        match self.ram_rtc_select {
+            0x08..=0x0C if self.rtc.is_some() => Some(self.ram_rtc_select),
+            _ => None,
+        }
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.mem.read_rom_bank(0, address),
+            _ => self.mem.read_rom_bank(self.rom_bank as usize, address),
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enable = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = value & 0b0111_1111;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_rtc_select = value & 0x0F,
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write_latch(value);
+                }
+            }
+            _ => unreachable!("MBC3 register write outside of 0000-7FFF"),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if let Some(register) = self.selected_rtc_register() {
+            if !self.ram_enable {
+                return 0xFF;
+            }
+            return self.rtc.as_ref().unwrap().read(register);
+        }
+        //selecting a bank past 3 that isnt a clock register maps nothing
+        if self.ram_rtc_select > 0x03 {
+            return 0xFF;
+        }
+        self.mem
+            .read_ram_bank(self.ram_enable, self.ram_rtc_select as usize, address)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(register) = self.selected_rtc_register() {
+            if self.ram_enable {
+                self.rtc.as_mut().unwrap().write(register, value);
+            }
+            return;
+        }
+        if self.ram_rtc_select > 0x03 {
+            return;
+        }
+        let bank = self.ram_rtc_select as usize;
+        self.mem
+            .write_ram_bank(self.ram_enable, bank, address, value)
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.mem.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.mem.ram
+    }
+    fn rtc(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+}
+
+/*MBC3 real time clock
+08  RTC S   Seconds   0-59 (0-3Bh)
+09  RTC M   Minutes   0-59 (0-3Bh)
+0A  RTC H   Hours     0-23 (0-17h)
+0B  RTC DL  Lower 8 bits of Day Counter (0-FFh)
+0C  RTC DH  Upper 1 bit of Day Counter, Carry Bit, Halt Flag
+      Bit 0  Most significant bit of Day Counter (Bit 8)
+      Bit 6  Halt (0=Active, 1=Stop Timer)
+      Bit 7  Day Counter Carry Bit (1=Counter Overflow)
+
+the real thing has its own crystal and keeps ticking while the gameboy is off. we fake that by remembering the host
+time we last caught the clock up at, and catching up by however much wall clock time has gone by whenever the game
+looks at it. that way the same thing works across the emulator being closed, as long as last_sync gets saved*/
+#[derive(Debug, Clone)]
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halt: bool,
+    day_carry: bool,
+    //S, M, H, DL, DH as of the last latch, this is what the game actually reads
+    latched: [u8; 5],
+    //last write to the latch register was a 0, so a 1 will latch
+    latch_armed: bool,
+    last_sync: std::time::SystemTime,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halt: false,
+            day_carry: false,
+            latched: [0; 5],
+            latch_armed: false,
+            last_sync: std::time::SystemTime::now(),
+        }
+    }
+
+    //bring the live counter up to date with the host clock
+    fn sync(&mut self) {
+        let now = std::time::SystemTime::now();
+        if self.halt {
+            //time doesnt pass while halted, so just move our reference point along
+            self.last_sync = now;
+            return;
+        }
+        //host clock going backwards (ntp, timezone fiddling, etc) just means no time passes
+        let elapsed = match now.duration_since(self.last_sync) {
+            Ok(elapsed) => elapsed.as_secs(),
+            Err(_) => {
+                self.last_sync = now;
+                return;
+            }
+        };
+        if elapsed == 0 {
+            return;
+        }
+        self.advance(elapsed);
+        //only move forward by whole seconds so we dont lose the fractional part
+        self.last_sync += std::time::Duration::from_secs(elapsed);
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        let total = self.seconds as u64
+            + seconds
+            + self.minutes as u64 * 60
+            + self.hours as u64 * 3600
+            + self.days as u64 * 86400;
+
+        self.seconds = (total % 60) as u8;
+        self.minutes = ((total / 60) % 60) as u8;
+        self.hours = ((total / 3600) % 24) as u8;
+        let days = total / 86400;
+        //the day counter is only 9 bits, and sets the carry bit (which stays set until the game clears it) on overflow
+        if days > 0x1FF {
+            self.day_carry = true;
+        }
+        self.days = (days % 0x200) as u16;
+    }
+
+    fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.sync();
+            self.latched = self.live_registers();
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    fn live_registers(&self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            (self.days & 0xFF) as u8,
+            self.dh(),
+        ]
+    }
+
+    fn dh(&self) -> u8 {
+        let mut value = ((self.days >> 8) & 0x1) as u8;
+        if self.halt {
+            value |= 0b0100_0000;
+        }
+        if self.day_carry {
+            value |= 0b1000_0000;
+        }
+        value
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        self.latched[(register - 0x08) as usize]
+    }
+
+    //writes go to the live counter, not the latched copy
+    fn write(&mut self, register: u8, value: u8) {
+        self.sync();
+        match register {
+            0x08 => {
+                self.seconds = value & 0b0011_1111;
+                //writing seconds also resets the sub second divider
+                self.last_sync = std::time::SystemTime::now();
+            }
+            0x09 => self.minutes = value & 0b0011_1111,
+            0x0A => self.hours = value & 0b0001_1111,
+            0x0B => self.days = (self.days & 0x100) | value as u16,
+            0x0C => {
+                self.days = (self.days & 0xFF) | (((value & 0x1) as u16) << 8);
+                self.halt = (value & 0b0100_0000) != 0;
+                self.day_carry = (value & 0b1000_0000) != 0;
+            }
+            _ => unreachable!("write to an rtc register that doesnt exist"),
+        }
+    }
+
+    /*the footer everyone (VBA-M, BGB, mGBA, SameBoy...) tacks onto the end of the .sav for MBC3 carts:
+    5 x u32 LE  live S, M, H, DL, DH
+    5 x u32 LE  latched S, M, H, DL, DH
+    u64 LE      unix timestamp the live values were taken at
+    some older emulators only write a u32 timestamp, making it 44 bytes instead of 48*/
+    pub const FOOTER_LEN: usize = 48;
+
+    pub fn to_footer(&mut self) -> Vec<u8> {
+        self.sync();
+        let mut footer = Vec::with_capacity(Self::FOOTER_LEN);
+        for value in self.live_registers().iter().chain(self.latched.iter()) {
+            footer.extend_from_slice(&(*value as u32).to_le_bytes());
+        }
+        let timestamp = self
+            .last_sync
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        footer.extend_from_slice(&timestamp.to_le_bytes());
+        footer
+    }
+
+    pub fn from_footer(footer: &[u8]) -> Option<Self> {
+        if footer.len() != 44 && footer.len() != 48 {
+            return None;
+        }
+        let field = |i: usize| footer[i * 4];
+        let timestamp = if footer.len() == 48 {
+            u64::from_le_bytes(footer[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64
+        };
+
+        let mut rtc = Self::new();
+        rtc.seconds = field(0);
+        rtc.minutes = field(1);
+        rtc.hours = field(2);
+        rtc.days = field(3) as u16 | (((field(4) & 0x1) as u16) << 8);
+        rtc.halt = (field(4) & 0b0100_0000) != 0;
+        rtc.day_carry = (field(4) & 0b1000_0000) != 0;
+        for i in 0..5 {
+            rtc.latched[i] = field(5 + i);
+        }
+        rtc.last_sync = std::time::UNIX_EPOCH + std::time::Duration::from_secs(timestamp);
+        //catch up on however long the emulator was closed for
+        rtc.sync();
+        Some(rtc)
+    }
+}
+
+/*MBC5
+0000-1FFF   RAM enable (0xA in the low nibble enables)
+2000-2FFF   low 8 bits of the ROM bank number
+3000-3FFF   bit 8 of the ROM bank number
+4000-5FFF   RAM bank number, 0-F. on rumble carts bit 3 drives the motor instead, leaving 3 bits of bank
+6000-7FFF   nothing
+
+unlike the older mappers bank 0 really is bank 0 here, there is no 0->1 bump*/
+struct Mbc5 {
+    mem: CartMemory,
+    ram_enable: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    has_rumble: bool,
+    rumble: bool,
+}
+
+impl Mbc5 {
+    fn new(mem: CartMemory, has_rumble: bool) -> Self {
+        Self {
+            mem,
+            ram_enable: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            rumble: false,
+        }
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.mem.read_rom_bank(0, address),
+            _ => self.mem.read_rom_bank(self.rom_bank as usize, address),
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enable = (value & 0x0F) == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0x0FF) | (((value & 0x1) as u16) << 8)
+            }
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    let rumble = (value & 0b0000_1000) != 0;
+                    if rumble != self.rumble {
+                        debug!("rumble motor {}", if rumble { "on" } else { "off" });
+                    }
+                    self.rumble = rumble;
+                    self.ram_bank = value & 0b0000_0111;
+                } else {
+                    self.ram_bank = value & 0b0000_1111;
+                }
+            }
+            0x6000..=0x7FFF => {}
+            _ => unreachable!("MBC5 register write outside of 0000-7FFF"),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.mem
+            .read_ram_bank(self.ram_enable, self.ram_bank as usize, address)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        let bank = self.ram_bank as usize;
+        self.mem
+            .write_ram_bank(self.ram_enable, bank, address, value)
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.mem.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.mem.ram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //every bank starts with its own bank number so we can tell which one is mapped in
+    fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * 0x4000];
+        for bank in 0..banks {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom
+    }
+
+    fn memory(rom: Vec<u8>, ram_size: usize) -> CartMemory {
+        CartMemory {
+            rom,
+            ram: vec![0; ram_size],
+        }
+    }
+
+    #[test]
+    fn mbc1_bank_zero_maps_bank_one() {
+        let mut mbc = Mbc1::new(memory(numbered_rom(8), 0));
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_register(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 5);
+        assert_eq!(mbc.read_rom(0x0000), 0);
+    }
+
+    #[test]
+    fn mbc1_bank_number_wraps_to_rom_size() {
+        let mut mbc = Mbc1::new(memory(numbered_rom(8), 0));
+        //only 3 address lines are hooked up on a 128KiB rom
+        mbc.write_register(0x2000, 0x0D);
+        assert_eq!(mbc.read_rom(0x4000), 5);
+        //the 0->1 bump looks at the full 5 bits, so 0x10 is bank 0 on this rom, not bank 1
+        mbc.write_register(0x2000, 0x10);
+        assert_eq!(mbc.read_rom(0x4000), 0);
+    }
+
+    #[test]
+    fn mbc1_upper_bits_and_advanced_mode() {
+        let mut mbc = Mbc1::new(memory(numbered_rom(128), 0x8000));
+        mbc.write_register(0x2000, 0x02);
+        mbc.write_register(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x22);
+        //simple mode leaves 0000-3FFF on bank 0
+        assert_eq!(mbc.read_rom(0x0000), 0);
+
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x20);
+
+        //and the 2 bit register picks the ram bank too
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x42);
+        mbc.write_register(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_register(0x4000, 0x01);
+        assert_eq!(mbc.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn mbc1_ram_disabled_reads_high() {
+        let mut mbc = Mbc1::new(memory(numbered_rom(2), 0x2000));
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc1_multicart_uses_four_bit_banks() {
+        let mut rom = numbered_rom(64);
+        //a logo at the start of the first two games is what gives a multicart away
+        for game in [0x00, 0x10] {
+            rom[game * 0x4000 + 0x0104..game * 0x4000 + 0x0134].fill(0xCE);
+        }
+        let mut mbc = Mbc1::new(memory(rom, 0));
+        assert!(mbc.multicart);
+
+        mbc.write_register(0x4000, 0x01);
+        mbc.write_register(0x2000, 0x02);
+        assert_eq!(mbc.read_rom(0x4000), 0x12);
+        //bit 4 of the bank register isnt wired
+        mbc.write_register(0x2000, 0x13);
+        assert_eq!(mbc.read_rom(0x4000), 0x13);
+        //and advanced mode swaps in the start of the selected game
+        mbc.write_register(0x6000, 0x01);
+        mbc.write_register(0x4000, 0x03);
+        assert_eq!(mbc.read_rom(0x0000), 0x30);
+    }
+
+    fn rtc_mbc() -> Mbc3 {
+        let mut mbc = Mbc3::new(memory(numbered_rom(4), 0x8000), true);
+        mbc.write_register(0x0000, 0x0A);
+        mbc
+    }
+
+    fn latch(mbc: &mut Mbc3) {
+        mbc.write_register(0x6000, 0x00);
+        mbc.write_register(0x6000, 0x01);
+    }
+
+    fn read_rtc(mbc: &mut Mbc3, register: u8) -> u8 {
+        mbc.write_register(0x4000, register);
+        mbc.read_ram(0xA000)
+    }
+
+    fn write_rtc(mbc: &mut Mbc3, register: u8, value: u8) {
+        mbc.write_register(0x4000, register);
+        mbc.write_ram(0xA000, value);
+    }
+
+    #[test]
+    fn rtc_reads_only_change_on_latch() {
+        let mut mbc = rtc_mbc();
+        write_rtc(&mut mbc, 0x09, 42);
+        assert_eq!(read_rtc(&mut mbc, 0x09), 0);
+        //a 1 without a 0 before it doesnt latch
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(read_rtc(&mut mbc, 0x09), 0);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x09), 42);
+    }
+
+    #[test]
+    fn rtc_catches_up_with_wall_clock() {
+        let mut mbc = rtc_mbc();
+        let rtc = mbc.rtc.as_mut().unwrap();
+        rtc.last_sync -= std::time::Duration::from_secs(3 * 3600 + 2 * 60 + 1);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 1);
+        assert_eq!(read_rtc(&mut mbc, 0x09), 2);
+        assert_eq!(read_rtc(&mut mbc, 0x0A), 3);
+    }
+
+    #[test]
+    fn rtc_halt_stops_time() {
+        let mut mbc = rtc_mbc();
+        write_rtc(&mut mbc, 0x0C, 0b0100_0000);
+        mbc.rtc.as_mut().unwrap().last_sync -= std::time::Duration::from_secs(100);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
+        assert_eq!(read_rtc(&mut mbc, 0x0C), 0b0100_0000);
+    }
+
+    #[test]
+    fn rtc_day_counter_carries() {
+        let mut mbc = rtc_mbc();
+        //day 0x1FF, 23:59:59
+        write_rtc(&mut mbc, 0x0B, 0xFF);
+        write_rtc(&mut mbc, 0x0C, 0x01);
+        write_rtc(&mut mbc, 0x0A, 23);
+        write_rtc(&mut mbc, 0x09, 59);
+        write_rtc(&mut mbc, 0x08, 59);
+        mbc.rtc.as_mut().unwrap().advance(1);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
+        assert_eq!(read_rtc(&mut mbc, 0x0B), 0);
+        //day bit 8 wrapped to 0, carry set
+        assert_eq!(read_rtc(&mut mbc, 0x0C), 0b1000_0000);
+
+        //and it stays set until the game clears it
+        mbc.rtc.as_mut().unwrap().advance(86400);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x0B), 1);
+        assert_eq!(read_rtc(&mut mbc, 0x0C), 0b1000_0000);
+        write_rtc(&mut mbc, 0x0C, 0x00);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x0C), 0);
+    }
+
+    #[test]
+    fn mbc2_address_bit_8_picks_the_register() {
+        let mut mbc = Mbc2::new(memory(numbered_rom(16), 0));
+        //bit 8 set is the rom bank, even in the 0000-1FFF half
+        mbc.write_register(0x0100, 0x03);
+        assert_eq!(mbc.read_rom(0x4000), 3);
+        mbc.write_register(0x2100, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+
+        //bit 8 clear is ram enable, even in the 2000-3FFF half, and doesnt touch the bank
+        mbc.write_register(0x2000, 0x0A);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_ram(0xA000, 0x5C);
+        assert_eq!(mbc.read_ram(0xA000), 0xFC);
+        //the 512 nibbles echo all the way up
+        assert_eq!(mbc.read_ram(0xA200), 0xFC);
+    }
+
+    #[test]
+    fn mbc5_nine_bit_rom_bank() {
+        let mut rom = numbered_rom(512);
+        //bank 0x100 and bank 0 both start with a 0 otherwise
+        rom[0x100 * 0x4000 + 1] = 0xAA;
+        let mut mbc = Mbc5::new(memory(rom, 0), false);
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0);
+        assert_eq!(mbc.read_rom(0x4001), 0);
+        mbc.write_register(0x3000, 0x01);
+        assert_eq!(mbc.read_rom(0x4001), 0xAA);
+        mbc.write_register(0x2000, 0x23);
+        assert_eq!(mbc.read_rom(0x4000), 0x23);
+        assert_eq!(mbc.read_rom(0x4001), 0);
+        mbc.write_register(0x3000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0x23);
+    }
+
+    #[test]
+    fn mbc5_rumble_bit_isnt_a_ram_bank() {
+        let mut mbc = Mbc5::new(memory(numbered_rom(2), 0x20000), true);
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x01);
+        mbc.write_ram(0xA000, 0x77);
+        mbc.write_register(0x4000, 0x09);
+        assert!(mbc.rumble);
+        assert_eq!(mbc.read_ram(0xA000), 0x77);
+    }
+
+    //live S, M, H, DL, DH then latched S, M, H, DL, DH, then the timestamp however wide it is
+    fn footer(live: [u8; 5], latched: [u8; 5], timestamp: &[u8]) -> Vec<u8> {
+        let mut footer = Vec::new();
+        for value in live.iter().chain(latched.iter()) {
+            footer.extend_from_slice(&(*value as u32).to_le_bytes());
+        }
+        footer.extend_from_slice(timestamp);
+        footer
+    }
+
+    fn now_secs() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn rtc_footer_with_64_bit_timestamp() {
+        let footer = footer(
+            [10, 20, 5, 3, 0x01],
+            [1, 2, 3, 4, 0x00],
+            &now_secs().to_le_bytes(),
+        );
+        assert_eq!(footer.len(), Rtc::FOOTER_LEN);
+        let mut rtc = Rtc::from_footer(&footer).unwrap();
+        assert_eq!(rtc.latched, [1, 2, 3, 4, 0x00]);
+        assert_eq!(rtc.minutes, 20);
+        assert_eq!(rtc.hours, 5);
+        assert_eq!(rtc.days, 0x103);
+
+        //and back out the same way
+        let out = rtc.to_footer();
+        assert_eq!(out.len(), Rtc::FOOTER_LEN);
+        assert_eq!(out[20..40], footer[20..40]);
+    }
+
+    #[test]
+    fn rtc_footer_with_32_bit_timestamp() {
+        //an hour ago, on a halted clock so nothing moves while we were closed
+        let footer = footer(
+            [10, 20, 5, 3, 0x40],
+            [0; 5],
+            &((now_secs() - 3600) as u32).to_le_bytes(),
+        );
+        assert_eq!(footer.len(), 44);
+        let rtc = Rtc::from_footer(&footer).unwrap();
+        assert!(rtc.halt);
+        assert_eq!(rtc.live_registers(), [10, 20, 5, 3, 0x40]);
+
+        //running, that hour gets caught up on load
+        let mut footer = footer;
+        footer[16] = 0x00;
+        let rtc = Rtc::from_footer(&footer).unwrap();
+        assert_eq!(rtc.hours, 6);
+        assert_eq!(rtc.minutes, 20);
+    }
+
+    #[test]
+    fn rtc_footer_bad_length() {
+        assert!(Rtc::from_footer(&[]).is_none());
+        assert!(Rtc::from_footer(&[0; 40]).is_none());
+        assert!(Rtc::from_footer(&[0; 52]).is_none());
+    }
+}
@@ -1,5 +1,7 @@
-use std::default;
+use std::{collections::VecDeque, default};
 
+use crate::bus::Bus;
+use crate::interrupt::{Interrupt, InterruptController};
 use crate::system::ExecutionError;
 
 /*$FF40	LCDC    LCD control                 	R/W	All
@@ -15,7 +17,27 @@ $FF49	OBP1	OBJ palette 1 data	            R/W	DMG
 $FF4A	WY	    Window Y position	            R/W	All
 $FF4B	WX	    Window X position plus 7	    R/W	All */
 
-#[derive(Debug, Clone, Copy, Default)]
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+/*every line is 456 dots (T cycles) long, 154 lines a frame
+   |<- 80 ->|<------ 172-289 ------>|<----- rest ----->|
+   | mode 2 |        mode 3         |      mode 0      |   lines 0-143
+   |                     mode 1                        |   lines 144-153
+mode 3 is as long as it takes the pixel fifo to push out 160 pixels, and hblank soaks up whatever is left*/
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SEARCH_DOTS: u16 = 80;
+const LINES_PER_FRAME: u8 = 154;
+
+//the 4 dmg shades as rgb, lightest to darkest
+const SHADES: [[u8; 3]; 4] = [
+    [0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+    [0x00, 0x00, 0x00],
+];
+
+#[derive(Debug, Clone, Default)]
 pub struct Ppu {
     LCDC: ppuctrl,
     STAT: ppustat,
@@ -29,8 +51,97 @@ pub struct Ppu {
     OBP1: u8,
     WY: u8,
     WX: u8,
+
+    //where we are in the current line, 0-455
+    dot: u16,
+    //how many pixels have made it to the screen on this line
+    lcd_x: u8,
+    //SCX%8 pixels get shifted out of the fifo and thrown away at the start of every line
+    discard: u8,
+    fetcher: Fetcher,
+    //color indices (0-3) waiting to be shifted out to the screen
+    bg_fifo: VecDeque<u8>,
+    //object pixels, shifted out alongside the bg fifo and mixed on top of it
+    obj_fifo: VecDeque<ObjPixel>,
+    //up to 10 objects found by the oam scan for this line
+    line_objects: Vec<Object>,
+    //dots left before the fetcher and pixel output can carry on after fetching objects
+    obj_stall: u8,
+
+    //the window keeps its own line counter, which only moves on lines where the window actually got drawn.
+    //turning it off for a few lines and back on picks up where it left off instead of skipping ahead
+    window_line: u8,
+    //LY has matched WY at some point this frame. once this is set the window can show up on any later line
+    wy_triggered: bool,
+    //the window started somewhere on the current line
+    window_on_line: bool,
+
+    //all the STAT interrupt sources get OR'd onto one line, and the interrupt is only requested when that line
+    //goes from low to high. so if one source is holding it high, another one firing does nothing ("STAT blocking")
+    stat_line: bool,
+    //a STAT write just tripped the write glitch below. the ppu doesnt hold the interrupt controller outside of
+    //tick, so whoever did the write picks this up with take_stat_write_glitch and raises the interrupt
+    stat_write_glitch: bool,
+
+    //the lcd was just turned off and the frontend still needs a blank frame
+    blank_pending: bool,
+    //the first frame after turning the lcd on never makes it to the screen
+    first_frame: bool,
+    //and the first line of it skips reporting mode 2, it just sits in mode 0 until mode 3
+    first_line: bool,
+}
+
+/*OAM, 40 entries of 4 bytes each
+Byte 0  Y position + 16
+Byte 1  X position + 8
+Byte 2  tile index, always 8000 addressing. bit 0 is ignored for 8x16 objects
+Byte 3  attributes
+          Bit 7  BG and Window over OBJ (0=No, 1=BG and Window colors 1-3 are drawn over the OBJ)
+          Bit 6  Y flip
+          Bit 5  X flip
+          Bit 4  DMG palette (0=OBP0, 1=OBP1)*/
+#[derive(Debug, Clone, Copy)]
+struct Object {
+    y: u8,
+    x: u8,
+    tile: u8,
+    attrs: u8,
+    //8 or 16, as of the oam scan. a game flipping the OBJ size mid line cant change which objects made it onto
+    //the line, so the fetch has to stick with the height they were picked with
+    height: u8,
+    //already pushed into the object fifo on this line
+    fetched: bool,
 }
 
+impl Object {
+    fn bg_priority(&self) -> bool {
+        (self.attrs & 0b1000_0000) != 0
+    }
+    fn y_flip(&self) -> bool {
+        (self.attrs & 0b0100_0000) != 0
+    }
+    fn x_flip(&self) -> bool {
+        (self.attrs & 0b0010_0000) != 0
+    }
+    fn palette(&self) -> bool {
+        (self.attrs & 0b0001_0000) != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ObjPixel {
+    //0 is transparent
+    color: u8,
+    //false = OBP0, true = OBP1
+    palette: bool,
+    bg_priority: bool,
+}
+
+//how long fetching an object holds up mode 3. the real thing is 6-11 dots depending on where the bg fetcher is
+const OBJ_FETCH_DOTS: u8 = 6;
+//the oam scan only keeps the first 10 objects on a line, in oam order
+const OBJS_PER_LINE: usize = 10;
+
 /*
 7	LCD and PPU enable	0=Off, 1=On
 6	Window tile map area	0=9800-9BFF, 1=9C00-9FFF
@@ -117,8 +228,8 @@ struct ppustat {
     Mode: PpuMode,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
-enum PpuMode {
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PpuMode {
     #[default]
     Hblank,
     VBlank,
@@ -171,6 +282,467 @@ impl Into<u8> for ppustat {
         value
     }
 }
+/*the background fetcher. every step takes 2 dots except push, which keeps retrying every dot until the fifo has
+room (on DMG that means until it is completely empty)
+Tile      read the tile number out of the tilemap
+DataLow   read the low bitplane of the tile row
+DataHigh  read the high bitplane of the tile row
+Push      push all 8 pixels of the row into the fifo*/
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum FetchStep {
+    #[default]
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Fetcher {
+    step: FetchStep,
+    //each step takes 2 dots, this is set on the first one
+    half_done: bool,
+    //the first fetch on every line is done and then thrown away, which we just model as sitting idle for long
+    //enough that mode 3 comes out at 172 dots with no scrolling or objects
+    stall: u8,
+    //fetching window tiles instead of background
+    window: bool,
+    //which tile column of the line (or of the window) we are fetching
+    x: u8,
+    tile: u8,
+    //row within the tile, 0-7
+    row: u8,
+    low: u8,
+    high: u8,
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    //runs the ppu for some number of dots (T cycles). vram and oam are the system's, and frame is the rgb
+    //framebuffer we draw into. returns true when we just entered vblank, meaning frame holds a finished picture
+    pub fn tick(
+        &mut self,
+        dots: usize,
+        vram: &[u8],
+        oam: &[u8],
+        interrupts: &mut InterruptController,
+        frame: &mut [u8],
+    ) -> bool {
+        if !self.LCDC.enable {
+            //a switched off lcd shows nothing at all, which is whiter than white 0
+            if self.blank_pending {
+                self.blank_pending = false;
+                frame.fill(0xFF);
+                return true;
+            }
+            return false;
+        }
+
+        let mut frame_done = false;
+        for _ in 0..dots {
+            frame_done |= self.tick_dot(vram, oam, interrupts, frame);
+        }
+        frame_done
+    }
+
+    fn tick_dot(
+        &mut self,
+        vram: &[u8],
+        oam: &[u8],
+        interrupts: &mut InterruptController,
+        frame: &mut [u8],
+    ) -> bool {
+        let mut frame_done = false;
+
+        //mode changes at the start of a line
+        if self.dot == 0 {
+            if self.LY == 0 {
+                self.window_line = 0;
+                self.wy_triggered = false;
+            }
+            if self.LY < SCREEN_HEIGHT as u8 {
+                //WY is compared every line, so a game moving it mid frame still gets picked up
+                if self.LY == self.WY {
+                    self.wy_triggered = true;
+                }
+                if !self.first_line {
+                    self.STAT.Mode = PpuMode::OAMsearch;
+                }
+            } else if self.LY == SCREEN_HEIGHT as u8 {
+                self.STAT.Mode = PpuMode::VBlank;
+                interrupts.request(Interrupt::VBlank);
+                //the vblank interrupt still happens, the frame just doesnt get shown
+                frame_done = !self.first_frame;
+                self.first_frame = false;
+            }
+        }
+
+        self.update_stat_line(interrupts);
+
+        if self.LY < SCREEN_HEIGHT as u8 && self.dot == OAM_SEARCH_DOTS {
+            self.first_line = false;
+            self.oam_scan(oam);
+            self.start_transfer();
+        }
+
+        if self.STAT.Mode == PpuMode::Transfer {
+            self.transfer_dot(vram, frame);
+            if self.lcd_x as usize == SCREEN_WIDTH {
+                self.STAT.Mode = PpuMode::Hblank;
+            }
+        }
+
+        self.dot += 1;
+        if self.dot == DOTS_PER_LINE {
+            if self.window_on_line {
+                self.window_line += 1;
+            }
+            self.dot = 0;
+            self.LY = (self.LY + 1) % LINES_PER_FRAME;
+            self.compare_lyc();
+        }
+
+        frame_done
+    }
+
+    pub fn mode(&self) -> PpuMode {
+        self.STAT.Mode
+    }
+
+    //the ppu owns the vram bus while it is pushing pixels, and the cpu gets 0xFF back (writes are dropped)
+    pub fn vram_locked(&self) -> bool {
+        self.STAT.Mode == PpuMode::Transfer
+    }
+
+    //same for OAM, which is also in use during the oam scan
+    pub fn oam_locked(&self) -> bool {
+        matches!(self.STAT.Mode, PpuMode::OAMsearch | PpuMode::Transfer)
+    }
+
+    fn write_lcdc(&mut self, value: u8) {
+        let was_enabled = self.LCDC.enable;
+        self.LCDC = value.into();
+
+        if was_enabled && !self.LCDC.enable {
+            //real hardware can get damaged doing this outside of vblank, so games arent supposed to
+            if self.STAT.Mode != PpuMode::VBlank {
+                warn!("lcd turned off outside of vblank, on line {}", self.LY);
+            }
+            //everything stops where it is, then LY goes back to 0 and the ppu sits in mode 0
+            self.LY = 0;
+            self.dot = 0;
+            self.STAT.Mode = PpuMode::Hblank;
+            self.compare_lyc();
+            self.blank_pending = true;
+        } else if !was_enabled && self.LCDC.enable {
+            //starts right at the top of a frame
+            self.LY = 0;
+            self.dot = 0;
+            self.compare_lyc();
+            self.first_frame = true;
+            self.first_line = true;
+        }
+    }
+
+    fn compare_lyc(&mut self) {
+        self.STAT.LYCLY = self.LY == self.LYC;
+    }
+
+    fn stat_sources(&self) -> bool {
+        let mode = match self.STAT.Mode {
+            PpuMode::Hblank => self.STAT.Mode0_int,
+            //the mode 2 source also fires at the very start of line 144, as if vblank started with an oam scan
+            PpuMode::VBlank => {
+                self.STAT.Mode1_int
+                    || (self.STAT.Mode2_int && self.LY == SCREEN_HEIGHT as u8 && self.dot == 0)
+            }
+            PpuMode::OAMsearch => self.STAT.Mode2_int,
+            PpuMode::Transfer => false,
+        };
+        mode || (self.STAT.LYCLY_int && self.STAT.LYCLY)
+    }
+
+    fn update_stat_line(&mut self, interrupts: &mut InterruptController) {
+        let line = self.stat_sources();
+        if line && !self.stat_line {
+            interrupts.request(Interrupt::Stat);
+        }
+        self.stat_line = line;
+    }
+
+    /*DMG quirk: for a cycle while STAT is being written, every enable bit reads as set. so writing STAT at all
+    during hblank, vblank or with LY=LYC fires a STAT interrupt (if the line wasnt already high), whatever
+    value is being written. some games trip over this and need it to work*/
+    fn write_stat(&mut self, value: u8) {
+        let glitch_line = match self.STAT.Mode {
+            PpuMode::Hblank | PpuMode::VBlank => true,
+            _ => self.STAT.LYCLY,
+        };
+        if self.LCDC.enable && glitch_line && !self.stat_line {
+            self.stat_write_glitch = true;
+        }
+
+        //the mode and LYC=LY flag are read only
+        let mut stat: ppustat = value.into();
+        stat.Mode = self.STAT.Mode;
+        stat.LYCLY = self.STAT.LYCLY;
+        self.STAT = stat;
+    }
+
+    //true (once) if the last STAT write should raise a STAT interrupt
+    pub fn take_stat_write_glitch(&mut self) -> bool {
+        std::mem::take(&mut self.stat_write_glitch)
+    }
+
+    fn obj_height(&self) -> u8 {
+        if self.LCDC.OBJ_size {
+            16
+        } else {
+            8
+        }
+    }
+
+    //picks out the objects that overlap this line. X doesnt matter here, an object sitting off screen
+    //still uses up one of the 10 slots
+    fn oam_scan(&mut self, oam: &[u8]) {
+        self.line_objects.clear();
+        let height = self.obj_height();
+        for entry in oam.chunks_exact(4) {
+            let row = self.LY as i16 + 16 - entry[0] as i16;
+            if row >= 0 && row < height as i16 {
+                self.line_objects.push(Object {
+                    y: entry[0],
+                    x: entry[1],
+                    tile: entry[2],
+                    attrs: entry[3],
+                    height,
+                    fetched: false,
+                });
+                if self.line_objects.len() == OBJS_PER_LINE {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn start_transfer(&mut self) {
+        self.STAT.Mode = PpuMode::Transfer;
+        self.lcd_x = 0;
+        self.discard = self.SCX % 8;
+        self.bg_fifo.clear();
+        self.obj_fifo.clear();
+        self.obj_stall = 0;
+        self.window_on_line = false;
+        self.fetcher = Fetcher {
+            stall: 7,
+            ..Default::default()
+        };
+    }
+
+    //one dot of mode 3: fetch any objects that start here, run the fetcher, then shift a pixel out to the lcd
+    fn transfer_dot(&mut self, vram: &[u8], frame: &mut [u8]) {
+        if self.obj_stall > 0 {
+            self.obj_stall -= 1;
+            return;
+        }
+        if self.discard == 0 && self.LCDC.OBJ_enable && self.fetch_objects(vram) {
+            return;
+        }
+        //WX is checked every dot rather than latched, so games can move it mid line or mid frame
+        if self.window_starts_here() {
+            self.start_window();
+        }
+
+        self.fetcher_dot(vram);
+
+        let Some(color) = self.bg_fifo.pop_front() else {
+            return;
+        };
+        if self.discard > 0 {
+            self.discard -= 1;
+            return;
+        }
+
+        //object pixels win unless they are transparent, or they are behind a non zero bg pixel
+        let shade = match self.obj_fifo.pop_front() {
+            Some(obj) if obj.color != 0 && !(obj.bg_priority && color != 0) => {
+                let palette = if obj.palette { self.OBP1 } else { self.OBP0 };
+                (palette >> (obj.color * 2)) & 0b11
+            }
+            _ => (self.BGP >> (color * 2)) & 0b11,
+        };
+        let offset = (self.LY as usize * SCREEN_WIDTH + self.lcd_x as usize) * 3;
+        frame[offset..offset + 3].copy_from_slice(&SHADES[shade as usize]);
+        self.lcd_x += 1;
+    }
+
+    fn fetcher_dot(&mut self, vram: &[u8]) {
+        if self.fetcher.stall > 0 {
+            self.fetcher.stall -= 1;
+            return;
+        }
+
+        //push is the only step that isnt 2 dots
+        if self.fetcher.step != FetchStep::Push && !self.fetcher.half_done {
+            self.fetcher.half_done = true;
+            return;
+        }
+        self.fetcher.half_done = false;
+
+        match self.fetcher.step {
+            FetchStep::Tile => {
+                let (map_select, x, y) = if self.fetcher.window {
+                    (
+                        self.LCDC.tilemap_area,
+                        self.fetcher.x & 0x1F,
+                        self.window_line,
+                    )
+                } else {
+                    (
+                        self.LCDC.BG_tilemap_area,
+                        (self.fetcher.x + self.SCX / 8) & 0x1F,
+                        self.LY.wrapping_add(self.SCY),
+                    )
+                };
+                let map = if map_select { 0x9C00 } else { 0x9800 };
+                self.fetcher.tile = vram_read(vram, map + (y as u16 / 8) * 32 + x as u16);
+                self.fetcher.row = y % 8;
+                self.fetcher.step = FetchStep::DataLow;
+            }
+            FetchStep::DataLow => {
+                let address = self.tile_data_address(self.fetcher.tile, self.fetcher.row);
+                self.fetcher.low = vram_read(vram, address);
+                self.fetcher.step = FetchStep::DataHigh;
+            }
+            FetchStep::DataHigh => {
+                let address = self.tile_data_address(self.fetcher.tile, self.fetcher.row);
+                self.fetcher.high = vram_read(vram, address + 1);
+                self.fetcher.step = FetchStep::Push;
+            }
+            FetchStep::Push => {
+                if !self.bg_fifo.is_empty() {
+                    return;
+                }
+                for bit in (0..8).rev() {
+                    let color =
+                        (((self.fetcher.high >> bit) & 1) << 1) | ((self.fetcher.low >> bit) & 1);
+                    //with the bg/window off they just come out as color 0
+                    self.bg_fifo
+                        .push_back(if self.LCDC.BGWindow_enable { color } else { 0 });
+                }
+                self.fetcher.x += 1;
+                self.fetcher.step = FetchStep::Tile;
+            }
+        }
+    }
+
+    /*the window covers everything from screen x = WX-7 to the right edge, on every line from WY down.
+    WX 0-6 would put its left edge off screen, so it starts right away at x 0 with the first 7-WX pixels
+    cut off instead. WX 167 and up puts it past the right edge, where it never starts at all*/
+    fn window_starts_here(&self) -> bool {
+        self.LCDC.window_enable
+            && self.wy_triggered
+            && !self.window_on_line
+            && self.WX <= 166
+            && self.lcd_x as u16 + 7 >= self.WX as u16
+    }
+
+    //throws away whatever background is queued up and restarts the fetcher on the window tilemap
+    fn start_window(&mut self) {
+        self.window_on_line = true;
+        self.bg_fifo.clear();
+        self.discard = 7u8.saturating_sub(self.WX);
+        self.fetcher = Fetcher {
+            window: true,
+            ..Default::default()
+        };
+    }
+
+    //fetches every object that starts at the current x into the object fifo. returns true if that stalled mode 3.
+    //they go in X order (then oam order for ties), and an object only fills in pixels that are still transparent,
+    //which is what gives the DMG rule of the lower X (then lower oam index) winning when objects overlap
+    fn fetch_objects(&mut self, vram: &[u8]) -> bool {
+        let mut hits: Vec<usize> = (0..self.line_objects.len())
+            .filter(|i| {
+                let obj = &self.line_objects[*i];
+                !obj.fetched && obj.x as u16 <= self.lcd_x as u16 + 8
+            })
+            .collect();
+        if hits.is_empty() {
+            return false;
+        }
+        //sort is stable, so objects at the same X stay in oam order
+        hits.sort_by_key(|i| self.line_objects[*i].x);
+
+        for i in &hits {
+            self.line_objects[*i].fetched = true;
+            let obj = self.line_objects[*i];
+            self.push_object(obj, vram);
+        }
+        //this dot is the first of the stall
+        self.obj_stall = OBJ_FETCH_DOTS * hits.len() as u8 - 1;
+        true
+    }
+
+    fn push_object(&mut self, obj: Object, vram: &[u8]) {
+        let height = obj.height;
+        //the oam scan already made sure this line is inside the object
+        let mut row = self.LY + 16 - obj.y;
+        if obj.y_flip() {
+            row = height - 1 - row;
+        }
+        //8x16 objects are a pair of tiles, the top one always even. rows 8-15 just run into the second tile
+        let tile = if height == 16 {
+            obj.tile & 0xFE
+        } else {
+            obj.tile
+        };
+        let address = 0x8000 + tile as u16 * 16 + row as u16 * 2;
+        let low = vram_read(vram, address);
+        let high = vram_read(vram, address + 1);
+
+        while self.obj_fifo.len() < 8 {
+            self.obj_fifo.push_back(ObjPixel::default());
+        }
+        for i in 0..8u8 {
+            //objects with X < 8 hang off the left edge, and those pixels just never show up
+            let screen_x = obj.x as i16 - 8 + i as i16;
+            if screen_x < self.lcd_x as i16 {
+                continue;
+            }
+            let bit = if obj.x_flip() { i } else { 7 - i };
+            let color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+            let slot = &mut self.obj_fifo[(screen_x - self.lcd_x as i16) as usize];
+            if slot.color == 0 {
+                *slot = ObjPixel {
+                    color,
+                    palette: obj.palette(),
+                    bg_priority: obj.bg_priority(),
+                };
+            }
+        }
+    }
+
+    //8000 addressing uses the tile number as unsigned from 8000, 8800 addressing uses it as signed from 9000
+    fn tile_data_address(&self, tile: u8, row: u8) -> u16 {
+        let base = if self.LCDC.BGWindow_area {
+            0x8000 + tile as u16 * 16
+        } else {
+            (0x9000 + (tile as i8 as i32) * 16) as u16
+        };
+        base + row as u16 * 2
+    }
+}
+
+fn vram_read(vram: &[u8], address: u16) -> u8 {
+    vram[(address - 0x8000) as usize]
+}
+
 /*$FF40	LCDC    LCD control                 	R/W	All
 $FF41	STAT    LCD status	                    Mixed	All
 $FF42	SCY	    Viewport Y position	            R/W	All
@@ -184,17 +756,17 @@ $FF49	OBP1	OBJ palette 1 data	            R/W	DMG
 $FF4A	WY	    Window Y position	            R/W	All
 $FF4B	WX	    Window X position plus 7	    R/W	All */
 
-impl Ppu {
-    //NOTE: these read and write functions vary slightly from every other signature in the codebase
-    //in that i only want to every read or write a single byte at a time (seeing as these are essentially MMIO regs)
-    //and dealing with writing across them would fucking suck
-    pub fn read(&mut self, address: u16) -> Result<u8, ExecutionError> {
+//NOTE: these are all single byte MMIO registers so Bus is a natural fit here, nobody ever has to care about
+//writing across them
+impl Bus for Ppu {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
         let value = match address {
             0xFF40 => self.LCDC.into(),
-            0xFF41 => self.STAT.into(),
+            //bit 7 isnt wired to anything
+            0xFF41 => Into::<u8>::into(self.STAT) | 0b1000_0000,
             0xFF42 => self.SCY,
             0xFF43 => self.SCX,
-            0xFF44 => 0x90, //self.LY,
+            0xFF44 => self.LY,
             0xFF45 => self.LYC,
             0xFF46 => self.DMA,
             0xFF47 => self.BGP,
@@ -206,23 +778,436 @@ impl Ppu {
         };
         Ok(value)
     }
-    pub fn write(&mut self, address: u16, data: u8) -> Result<usize, ExecutionError> {
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
         match address {
-            0xFF40 => self.LCDC = data.into(),
-            0xFF41 => self.STAT = data.into(),
-            0xFF42 => self.SCY = data,
-            0xFF43 => self.SCX = data,
-            0xFF44 => {} //self.LY = data,
-            0xFF45 => self.LYC = data,
-            0xFF46 => self.DMA = data,
-            0xFF47 => self.BGP = data,
-            0xFF48 => self.OBP0 = data,
-            0xFF49 => self.OBP1 = data,
-            0xFF4A => self.WY = data,
-            0xFF4B => self.WX = data,
-            _ => unreachable!("PPU trying to service a READ outside of its memory mapping range"),
+            0xFF40 => self.write_lcdc(value),
+            0xFF41 => self.write_stat(value),
+            0xFF42 => self.SCY = value,
+            0xFF43 => self.SCX = value,
+            //LY is read only
+            0xFF44 => {}
+            0xFF45 => {
+                self.LYC = value;
+                self.compare_lyc();
+            }
+            0xFF46 => self.DMA = value,
+            0xFF47 => self.BGP = value,
+            0xFF48 => self.OBP0 = value,
+            0xFF49 => self.OBP1 = value,
+            0xFF4A => self.WY = value,
+            0xFF4B => self.WX = value,
+            _ => unreachable!("PPU trying to service a WRITE outside of its memory mapping range"),
         };
 
-        Ok(1)
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOTS_PER_FRAME: usize = DOTS_PER_LINE as usize * LINES_PER_FRAME as usize;
+
+    //a ppu along with everything it needs from the rest of the system
+    struct TestPpu {
+        ppu: Ppu,
+        interrupts: InterruptController,
+        vram: Vec<u8>,
+        oam: Vec<u8>,
+        frame: Vec<u8>,
+    }
+
+    impl TestPpu {
+        fn new() -> Self {
+            let mut ppu = Ppu::new();
+            ppu.BGP = 0b11_10_01_00;
+            ppu.OBP0 = 0b11_10_01_00;
+            ppu.OBP1 = 0b11_10_01_00;
+            Self {
+                ppu,
+                interrupts: InterruptController::new(),
+                vram: vec![0; 0x2000],
+                oam: vec![0; 0xA0],
+                frame: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 3],
+            }
+        }
+
+        fn run(&mut self, dots: usize) -> bool {
+            self.ppu.tick(
+                dots,
+                &self.vram,
+                &self.oam,
+                &mut self.interrupts,
+                &mut self.frame,
+            )
+        }
+
+        //first byte of the rgb, which is enough to tell the shades apart
+        fn pixel(&self, x: usize, y: usize) -> u8 {
+            self.frame[(y * SCREEN_WIDTH + x) * 3]
+        }
+
+        //tile in 8000 addressing where every pixel is the same color
+        fn solid_tile(&mut self, tile: usize, color: u8) {
+            for row in 0..8 {
+                self.vram[tile * 16 + row * 2] = if (color & 1) != 0 { 0xFF } else { 0 };
+                self.vram[tile * 16 + row * 2 + 1] = if (color & 2) != 0 { 0xFF } else { 0 };
+            }
+        }
+
+        //dot and new mode every time the mode changes over the next line
+        fn line_modes(&mut self) -> Vec<(u16, PpuMode)> {
+            let mut modes = Vec::new();
+            let mut last = None;
+            for dot in 0..DOTS_PER_LINE {
+                self.run(1);
+                let mode = self.ppu.mode();
+                if last != Some(mode) {
+                    modes.push((dot, mode));
+                    last = Some(mode);
+                }
+            }
+            modes
+        }
+    }
+
+    #[test]
+    fn plain_line_mode_timing() {
+        let mut t = TestPpu::new();
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_LINE as usize);
+        assert_eq!(
+            t.line_modes(),
+            [
+                (0, PpuMode::OAMsearch),
+                (80, PpuMode::Transfer),
+                (252, PpuMode::Hblank)
+            ]
+        );
+    }
+
+    #[test]
+    fn scx_fine_scroll_lengthens_mode_3() {
+        let mut t = TestPpu::new();
+        t.ppu.SCX = 3;
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_LINE as usize);
+        assert_eq!(t.line_modes()[2], (255, PpuMode::Hblank));
+    }
+
+    #[test]
+    fn vblank_and_frame_timing() {
+        let mut t = TestPpu::new();
+        t.ppu.write_lcdc(0x91);
+        //the first frame after turning the lcd on is never shown, but still raises vblank
+        assert!(!t.run(DOTS_PER_FRAME));
+        assert_eq!(
+            t.interrupts.IF & Interrupt::VBlank.bit(),
+            Interrupt::VBlank.bit()
+        );
+        assert!(t.run(DOTS_PER_FRAME));
+        assert_eq!(t.ppu.LY, 0);
+        assert!(!t.run(DOTS_PER_LINE as usize * SCREEN_HEIGHT));
+        assert!(t.run(1));
+        assert_eq!(t.ppu.mode(), PpuMode::VBlank);
+    }
+
+    #[test]
+    fn background_tiles_and_scroll() {
+        let mut t = TestPpu::new();
+        t.solid_tile(1, 3);
+        //tile 1 in the second column of the first row of the map
+        t.vram[0x1800 + 1] = 1;
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_FRAME * 2);
+        assert_eq!(t.pixel(7, 0), 0xFF);
+        assert_eq!(t.pixel(8, 0), 0x00);
+        assert_eq!(t.pixel(15, 7), 0x00);
+        assert_eq!(t.pixel(16, 0), 0xFF);
+        assert_eq!(t.pixel(8, 8), 0xFF);
+
+        t.ppu.SCX = 3;
+        t.ppu.SCY = 2;
+        t.run(DOTS_PER_FRAME);
+        assert_eq!(t.pixel(4, 0), 0xFF);
+        assert_eq!(t.pixel(5, 0), 0x00);
+        assert_eq!(t.pixel(5, 5), 0x00);
+        assert_eq!(t.pixel(5, 6), 0xFF);
+    }
+
+    #[test]
+    fn signed_tile_addressing() {
+        let mut t = TestPpu::new();
+        //tile 0 in 8800 addressing lives at 9000, and tile 0x80 at 8800
+        for row in 0..8 {
+            t.vram[0x1000 + row * 2] = 0xFF;
+            t.vram[0x0800 + row * 2 + 1] = 0xFF;
+        }
+        t.vram[0x1800 + 1] = 0x80;
+        t.ppu.write_lcdc(0x81);
+        t.run(DOTS_PER_FRAME * 2);
+        assert_eq!(t.pixel(0, 0), 0xAA);
+        assert_eq!(t.pixel(8, 0), 0x55);
+    }
+
+    fn set_object(t: &mut TestPpu, index: usize, y: u8, x: u8, tile: u8, attrs: u8) {
+        t.oam[index * 4..index * 4 + 4].copy_from_slice(&[y, x, tile, attrs]);
+    }
+
+    #[test]
+    fn lower_x_object_wins() {
+        let mut t = TestPpu::new();
+        t.solid_tile(1, 3);
+        t.solid_tile(2, 1);
+        //oam order would put this one on top, but it starts further right
+        set_object(&mut t, 0, 16, 12, 2, 0);
+        set_object(&mut t, 1, 16, 10, 1, 0);
+        t.ppu.write_lcdc(0x93);
+        t.run(DOTS_PER_FRAME * 2);
+        assert_eq!(t.pixel(1, 0), 0xFF);
+        assert_eq!(t.pixel(2, 0), 0x00);
+        assert_eq!(t.pixel(9, 0), 0x00);
+        assert_eq!(t.pixel(10, 0), 0xAA);
+        assert_eq!(t.pixel(11, 0), 0xAA);
+        assert_eq!(t.pixel(12, 0), 0xFF);
+    }
+
+    #[test]
+    fn bg_priority_and_palettes() {
+        let mut t = TestPpu::new();
+        t.solid_tile(1, 1);
+        t.solid_tile(2, 2);
+        //bg tile 1 across the first 8 pixels, white after that
+        t.vram[0x1800] = 1;
+        t.ppu.OBP1 = 0b00_11_00_00;
+        //behind the bg, so only shows where the bg is color 0
+        set_object(&mut t, 0, 16, 12, 2, 0b1000_0000);
+        //OBP1 maps color 2 to black
+        set_object(&mut t, 1, 32, 8, 2, 0b0001_0000);
+        t.ppu.write_lcdc(0x93);
+        t.run(DOTS_PER_FRAME * 2);
+        assert_eq!(t.pixel(4, 0), 0xAA);
+        assert_eq!(t.pixel(8, 0), 0x55);
+        assert_eq!(t.pixel(0, 16), 0x00);
+    }
+
+    #[test]
+    fn tall_objects_and_flips() {
+        let mut t = TestPpu::new();
+        //tile 4 is color 1, tile 5 is color 3. 8x16 ignores bit 0 of the tile number
+        t.solid_tile(4, 1);
+        t.solid_tile(5, 3);
+        set_object(&mut t, 0, 16, 8, 5, 0);
+        set_object(&mut t, 1, 16, 16, 4, 0b0100_0000);
+        t.ppu.write_lcdc(0x97);
+        t.run(DOTS_PER_FRAME * 2);
+        assert_eq!(t.pixel(0, 0), 0xAA);
+        assert_eq!(t.pixel(0, 15), 0x00);
+        assert_eq!(t.pixel(0, 16), 0xFF);
+        //y flipped, so the bottom tile is on top
+        assert_eq!(t.pixel(8, 0), 0x00);
+        assert_eq!(t.pixel(8, 15), 0xAA);
+    }
+
+    #[test]
+    fn ten_objects_per_line() {
+        let mut t = TestPpu::new();
+        t.solid_tile(1, 3);
+        //the first one is off screen, but still takes up a slot
+        set_object(&mut t, 0, 16, 0, 1, 0);
+        for i in 1..12 {
+            set_object(&mut t, i, 16, 8 + 8 * i as u8, 1, 0);
+        }
+        t.ppu.write_lcdc(0x93);
+        t.run(DOTS_PER_FRAME * 2);
+        assert_eq!(t.pixel(9 * 8, 0), 0x00);
+        assert_eq!(t.pixel(10 * 8, 0), 0xFF);
+    }
+
+    #[test]
+    fn obj_size_change_mid_line() {
+        let mut t = TestPpu::new();
+        t.solid_tile(2, 1);
+        t.solid_tile(3, 3);
+        //line 12 is in the bottom half of this 8x16 object, which is the top tile once it is flipped
+        set_object(&mut t, 0, 16, 100, 2, 0b0100_0000);
+        t.ppu.write_lcdc(0x97);
+        t.run(DOTS_PER_FRAME + DOTS_PER_LINE as usize * 12 + 81);
+        //switching to 8x8 after the scan picked it up keeps fetching it as 8x16
+        t.ppu.write_lcdc(0x93);
+        t.run(DOTS_PER_LINE as usize);
+        assert_eq!(t.pixel(92, 12), 0xAA);
+        //and the next line is scanned as 8x8, where it doesnt reach
+        t.run(DOTS_PER_LINE as usize);
+        assert_eq!(t.pixel(92, 13), 0xFF);
+    }
+
+    //window map at 9C00 full of black tiles
+    fn window_setup() -> TestPpu {
+        let mut t = TestPpu::new();
+        t.solid_tile(1, 3);
+        t.vram[0x1C00..0x2000].fill(1);
+        t
+    }
+
+    #[test]
+    fn window_position() {
+        let mut t = window_setup();
+        t.ppu.WY = 10;
+        t.ppu.WX = 7 + 20;
+        t.ppu.write_lcdc(0xF1);
+        t.run(DOTS_PER_FRAME * 2);
+        assert_eq!(t.pixel(20, 9), 0xFF);
+        assert_eq!(t.pixel(19, 10), 0xFF);
+        assert_eq!(t.pixel(20, 10), 0x00);
+        assert_eq!(t.pixel(159, 143), 0x00);
+
+        //WX under 7 starts at the left edge
+        t.ppu.WX = 3;
+        t.run(DOTS_PER_FRAME);
+        assert_eq!(t.pixel(0, 10), 0x00);
+
+        //and past 166 it never shows up
+        t.ppu.WX = 167;
+        t.run(DOTS_PER_FRAME);
+        assert_eq!(t.pixel(159, 143), 0xFF);
+    }
+
+    #[test]
+    fn window_line_counter_skips_hidden_lines() {
+        let mut t = window_setup();
+        //only the first row of window tiles is black, the second row is tile 0
+        t.vram[0x1C20..0x2000].fill(0);
+        t.ppu.WY = 0;
+        t.ppu.WX = 7;
+        t.ppu.write_lcdc(0xF1);
+        t.run(DOTS_PER_FRAME);
+
+        //window on for 4 lines, off for 8, then back on. it picks up at its own line 4, which is still black
+        t.run(DOTS_PER_LINE as usize * 4);
+        t.ppu.write_lcdc(0xD1);
+        t.run(DOTS_PER_LINE as usize * 8);
+        t.ppu.write_lcdc(0xF1);
+        t.run(DOTS_PER_FRAME - DOTS_PER_LINE as usize * 12);
+        assert_eq!(t.pixel(0, 3), 0x00);
+        assert_eq!(t.pixel(0, 4), 0xFF);
+        assert_eq!(t.pixel(0, 12), 0x00);
+        assert_eq!(t.pixel(0, 15), 0x00);
+        assert_eq!(t.pixel(0, 16), 0xFF);
+    }
+
+    //line and dot of every STAT interrupt over the next few lines. the line is sampled at the start of a dot, so
+    //hblank shows up on the dot after mode 3 ends
+    fn stat_hits(t: &mut TestPpu, lines: usize) -> Vec<(u8, u16)> {
+        let mut hits = Vec::new();
+        for _ in 0..lines * DOTS_PER_LINE as usize {
+            let (line, dot) = (t.ppu.LY, t.ppu.dot);
+            t.run(1);
+            if (t.interrupts.IF & Interrupt::Stat.bit()) != 0 {
+                hits.push((line, dot));
+                t.interrupts.IF = 0;
+            }
+        }
+        hits
+    }
+
+    #[test]
+    fn stat_mode_sources() {
+        let mut t = TestPpu::new();
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_LINE as usize);
+        //mode 2 only
+        t.ppu.STAT.Mode2_int = true;
+        t.interrupts.IF = 0;
+        assert_eq!(stat_hits(&mut t, 2), [(1, 0), (2, 0)]);
+        //mode 0 only
+        t.ppu.STAT.Mode2_int = false;
+        t.ppu.STAT.Mode0_int = true;
+        assert_eq!(stat_hits(&mut t, 2), [(3, 253), (4, 253)]);
+    }
+
+    #[test]
+    fn stat_blocking() {
+        let mut t = TestPpu::new();
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_LINE as usize);
+        //hblank and mode 2 together: hblank holds the line high right into the next mode 2, so only the
+        //hblank edge ever fires
+        t.ppu.STAT.Mode0_int = true;
+        t.ppu.STAT.Mode2_int = true;
+        t.interrupts.IF = 0;
+        assert_eq!(stat_hits(&mut t, 2), [(1, 0), (1, 253), (2, 253)]);
+
+        //LYC=LY holding the line high blocks the hblank of that line
+        t.ppu.STAT.Mode2_int = false;
+        t.ppu.STAT.LYCLY_int = true;
+        t.ppu.LYC = 4;
+        assert_eq!(stat_hits(&mut t, 3), [(3, 253), (5, 253)]);
+    }
+
+    #[test]
+    fn stat_write_glitch() {
+        let mut t = TestPpu::new();
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_LINE as usize + 300);
+        assert_eq!(t.ppu.mode(), PpuMode::Hblank);
+        //any write during hblank fires, even one enabling nothing
+        t.ppu.write8(0xFF41, 0x00).unwrap();
+        assert!(t.ppu.take_stat_write_glitch());
+        assert!(!t.ppu.take_stat_write_glitch());
+
+        //but not in mode 3 with LY != LYC
+        t.ppu.LYC = 100;
+        t.run(DOTS_PER_LINE as usize - 300 + 100);
+        assert_eq!(t.ppu.mode(), PpuMode::Transfer);
+        t.ppu.write8(0xFF41, 0x00).unwrap();
+        assert!(!t.ppu.take_stat_write_glitch());
+    }
+
+    #[test]
+    fn lcd_off_blanks_and_resets() {
+        let mut t = TestPpu::new();
+        t.solid_tile(0, 3);
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_FRAME * 2 + DOTS_PER_LINE as usize * 150);
+        assert_eq!(t.pixel(0, 0), 0x00);
+
+        t.ppu.write_lcdc(0x11);
+        assert_eq!(t.ppu.LY, 0);
+        assert_eq!(t.ppu.mode(), PpuMode::Hblank);
+        //one white frame for the frontend, then nothing
+        assert!(t.run(4));
+        assert_eq!(t.pixel(0, 0), 0xFF);
+        assert!(!t.run(DOTS_PER_FRAME));
+        assert_eq!(t.ppu.LY, 0);
+
+        //back on, the first line skips mode 2 and the first frame doesnt get shown
+        t.ppu.write_lcdc(0x91);
+        t.run(1);
+        assert_eq!(t.ppu.mode(), PpuMode::Hblank);
+        t.run(80);
+        assert_eq!(t.ppu.mode(), PpuMode::Transfer);
+        assert!(!t.run(DOTS_PER_FRAME - 81));
+        assert!(t.run(DOTS_PER_FRAME));
+    }
+
+    #[test]
+    fn vram_and_oam_locking() {
+        let mut t = TestPpu::new();
+        assert!(!t.ppu.vram_locked() && !t.ppu.oam_locked());
+        t.ppu.write_lcdc(0x91);
+        t.run(DOTS_PER_LINE as usize + 1);
+        assert_eq!(t.ppu.mode(), PpuMode::OAMsearch);
+        assert!(!t.ppu.vram_locked() && t.ppu.oam_locked());
+        t.run(80);
+        assert_eq!(t.ppu.mode(), PpuMode::Transfer);
+        assert!(t.ppu.vram_locked() && t.ppu.oam_locked());
+        t.run(200);
+        assert_eq!(t.ppu.mode(), PpuMode::Hblank);
+        assert!(!t.ppu.vram_locked() && !t.ppu.oam_locked());
+        t.run(DOTS_PER_LINE as usize * 144);
+        assert_eq!(t.ppu.mode(), PpuMode::VBlank);
+        assert!(!t.ppu.vram_locked() && !t.ppu.oam_locked());
     }
 }
@@ -0,0 +1,442 @@
+use crate::bus::Bus;
+use crate::interrupt::{Interrupt, InterruptController};
+use crate::system::ExecutionError;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/*$FF01	SB	Serial transfer data	R/W	All
+$FF02	SC	Serial transfer control	R/W	Mixed
+
+SC
+Bit 7 - Transfer start (0=No transfer is in progress or requested, 1=Transfer in progress, or requested)
+Bit 0 - Shift clock    (0=External clock, 1=Internal clock)
+
+SB is a shift register. every clock pulse our top bit goes out and the other side's bit comes in at the bottom,
+so after 8 pulses the two gameboys have swapped bytes, bit 7 of SC clears and the serial interrupt fires.
+whoever uses the internal clock drives the pulses at 8192Hz, the other side sets the external clock and just
+waits, forever if nobody is there.
+
+when we drive the clock our byte goes out over the link as soon as the transfer starts, and the other side's byte
+gets shifted in a bit at a time once it has come back. the transfer cant finish until it has, so a slow link just
+stretches the transfer out instead of holding up the whole emulator. when the other side drives it we only ever
+see whole bytes*/
+
+//one bit goes out every 512 T cycles on the internal clock (8192Hz)
+const BIT_CYCLES: usize = 512;
+
+//whatever is on the other end of the link cable. none of these can block, they get called with the system locked
+pub trait SerialLink: Send {
+    //we are driving the clock and just started a transfer, send our byte
+    fn send(&mut self, out: u8);
+    //checked once a bit period after send until it hands back the other side's byte. links that give up waiting
+    //hand back 0xFF, same as nobody being there
+    fn reply(&mut self) -> Option<u8>;
+    //checked once a bit period when we arent driving the clock. if the other side clocked a byte over, answer
+    //with out and hand back what they sent
+    fn clocked(&mut self, out: u8) -> Option<u8>;
+}
+
+//nothing plugged in. the data line floats high and nobody ever drives the clock for us
+pub struct Disconnected;
+
+impl SerialLink for Disconnected {
+    fn send(&mut self, _out: u8) {}
+
+    fn reply(&mut self) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    fn clocked(&mut self, _out: u8) -> Option<u8> {
+        None
+    }
+}
+
+//nothing plugged in either, but keeps everything the game sends. blargg's test roms print their results this way
+#[derive(Default)]
+pub struct Capture {
+    output: Arc<Mutex<Vec<u8>>>,
+    line: Vec<u8>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    //everything sent so far, for whoever wants to read it while we keep running
+    pub fn output(&self) -> Arc<Mutex<Vec<u8>>> {
+        self.output.clone()
+    }
+}
+
+impl SerialLink for Capture {
+    fn send(&mut self, out: u8) {
+        self.output.lock().unwrap().push(out);
+        if out == b'\n' {
+            info!("serial: {}", String::from_utf8_lossy(&self.line));
+            self.line.clear();
+        } else {
+            self.line.push(out);
+        }
+    }
+
+    fn reply(&mut self) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    fn clocked(&mut self, _out: u8) -> Option<u8> {
+        None
+    }
+}
+
+//links two emulators over tcp. every message is 2 bytes, a tag and the data byte
+const MSG_CLOCK: u8 = 0x01;
+const MSG_REPLY: u8 = 0x02;
+//how long to wait on the other emulator before acting like nobody is there
+const LINK_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct TcpLink {
+    listener: Option<TcpListener>,
+    stream: Option<TcpStream>,
+    //bytes read that dont make up a whole message yet
+    rx: Vec<u8>,
+    //the byte we sent and when we give up on a reply, while we are driving a transfer
+    waiting: Option<(u8, Instant)>,
+}
+
+impl TcpLink {
+    //wait for the other emulator to connect to us. this doesnt block, the connection gets picked up whenever it
+    //shows up and until then we are disconnected
+    pub fn host(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: Some(listener),
+            stream: None,
+            rx: Vec::new(),
+            waiting: None,
+        })
+    }
+
+    pub fn connect(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        Self::setup(&stream)?;
+        Ok(Self {
+            listener: None,
+            stream: Some(stream),
+            rx: Vec::new(),
+            waiting: None,
+        })
+    }
+
+    //the messages are tiny and a transfer is waiting on every one of them, so no nagle
+    fn setup(stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)
+    }
+
+    fn accept(&mut self) {
+        if self.stream.is_some() {
+            return;
+        }
+        if let Some(listener) = &self.listener {
+            match listener.accept() {
+                Ok((stream, peer)) => match Self::setup(&stream) {
+                    Ok(()) => {
+                        info!("serial link connected to {peer}");
+                        self.stream = Some(stream);
+                        self.rx.clear();
+                    }
+                    Err(e) => warn!("failed to set up serial link with {peer}: {e}"),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => warn!("serial link accept failed: {e}"),
+            }
+        }
+    }
+
+    //anything already read stays in rx, the other side might have hung up right after replying
+    fn disconnect(&mut self, e: std::io::Error) {
+        warn!("serial link dropped: {e}");
+        self.stream = None;
+    }
+
+    //2 bytes into a local socket wont ever really block, so treat that like any other failure
+    fn send(&mut self, tag: u8, data: u8) {
+        let result = match &mut self.stream {
+            Some(stream) => stream.write_all(&[tag, data]),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.disconnect(e);
+        }
+    }
+
+    //the next message, if a whole one has come in
+    fn receive(&mut self) -> Option<(u8, u8)> {
+        let mut error = None;
+        if let Some(stream) = &mut self.stream {
+            let mut buf = [0u8; 64];
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) => {
+                        error = Some(std::io::Error::new(
+                            ErrorKind::UnexpectedEof,
+                            "other side closed the connection",
+                        ));
+                        break;
+                    }
+                    Ok(n) => self.rx.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(e) = error {
+            self.disconnect(e);
+        }
+
+        if self.rx.len() < 2 {
+            return None;
+        }
+        let message = (self.rx[0], self.rx[1]);
+        self.rx.drain(..2);
+        Some(message)
+    }
+}
+
+impl SerialLink for TcpLink {
+    fn send(&mut self, out: u8) {
+        self.accept();
+        self.waiting = Some((out, Instant::now() + LINK_TIMEOUT));
+        self.send(MSG_CLOCK, out);
+    }
+
+    fn reply(&mut self) -> Option<u8> {
+        let (out, deadline) = self.waiting?;
+        loop {
+            match self.receive() {
+                Some((MSG_REPLY, data)) => {
+                    self.waiting = None;
+                    return Some(data);
+                }
+                //they are driving the clock at the same time, answer them and keep waiting on our own reply
+                Some((MSG_CLOCK, _)) => self.send(MSG_REPLY, out),
+                Some((tag, _)) => warn!("serial link got a message with bad tag {tag:#04x}"),
+                None => break,
+            }
+        }
+        //nobody there (anymore) is the same as nobody answering
+        if self.stream.is_none() {
+            self.waiting = None;
+            return Some(0xFF);
+        }
+        if Instant::now() >= deadline {
+            warn!("serial link gave up waiting on the other side");
+            self.waiting = None;
+            return Some(0xFF);
+        }
+        None
+    }
+
+    fn clocked(&mut self, out: u8) -> Option<u8> {
+        self.accept();
+        match self.receive()? {
+            (MSG_CLOCK, data) => {
+                self.send(MSG_REPLY, out);
+                Some(data)
+            }
+            //a reply that showed up after we stopped waiting for it
+            _ => None,
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub struct Serial {
+    SB: u8,
+    SC: u8,
+    link: Box<dyn SerialLink>,
+    //T cycles into the current bit period
+    clock: usize,
+    //bits shifted so far in a transfer we are clocking
+    bits: u8,
+    //the byte the other side sent, shifted into SB a bit at a time. None until the link hands it back
+    incoming: Option<u8>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new(Box::new(Disconnected))
+    }
+}
+
+impl Serial {
+    pub fn new(link: Box<dyn SerialLink>) -> Self {
+        Self {
+            SB: 0,
+            SC: 0,
+            link,
+            clock: 0,
+            bits: 0,
+            incoming: None,
+        }
+    }
+
+    pub fn tick(&mut self, cycles: usize, interrupts: &mut InterruptController) {
+        self.clock += cycles;
+        while self.clock >= BIT_CYCLES {
+            self.clock -= BIT_CYCLES;
+            self.shift(interrupts);
+        }
+    }
+
+    fn transferring(&self) -> bool {
+        (self.SC & 0b1000_0000) != 0
+    }
+
+    fn internal_clock(&self) -> bool {
+        (self.SC & 0b0000_0001) != 0
+    }
+
+    fn shift(&mut self, interrupts: &mut InterruptController) {
+        if self.transferring() && self.internal_clock() {
+            if self.incoming.is_none() {
+                self.incoming = self.link.reply();
+            }
+            //until their byte shows up all we can shift in is the line floating high
+            if self.bits < 8 {
+                let bit = self
+                    .incoming
+                    .map_or(1, |byte| (byte >> (7 - self.bits)) & 1);
+                self.SB = (self.SB << 1) | bit;
+                self.bits += 1;
+            }
+            //all our bits are out, but the transfer only finishes once we know what came back
+            if self.bits == 8 {
+                if let Some(byte) = self.incoming {
+                    self.SB = byte;
+                    self.finish(interrupts);
+                }
+            }
+        } else if let Some(byte) = self.link.clocked(self.SB) {
+            //the other side clocked a whole byte through. the shift register only moves if we asked for a
+            //transfer, but they still get whatever is sitting in SB
+            if self.transferring() {
+                self.SB = byte;
+                self.finish(interrupts);
+            }
+        }
+    }
+
+    fn finish(&mut self, interrupts: &mut InterruptController) {
+        self.SC &= 0b0111_1111;
+        self.bits = 0;
+        interrupts.request(Interrupt::Serial);
+    }
+}
+
+impl Bus for Serial {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
+        match address {
+            0xFF01 => Ok(self.SB),
+            //only bits 7 and 0 exist on DMG
+            0xFF02 => Ok(self.SC | 0b0111_1110),
+            _ => {
+                unreachable!("serial trying to service a READ outside of its memory mapping range")
+            }
+        }
+    }
+
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
+        match address {
+            0xFF01 => self.SB = value,
+            0xFF02 => {
+                self.SC = value & 0b1000_0001;
+                if self.transferring() && self.internal_clock() {
+                    self.link.send(self.SB);
+                    self.incoming = None;
+                    self.bits = 0;
+                    self.clock = 0;
+                }
+            }
+            _ => {
+                unreachable!("serial trying to service a WRITE outside of its memory mapping range")
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_clock_transfer_takes_8_bits() {
+        let capture = Capture::new();
+        let output = capture.output();
+        let mut serial = Serial::new(Box::new(capture));
+        let mut interrupts = InterruptController::new();
+        serial.write8(0xFF01, b'O').unwrap();
+        serial.write8(0xFF02, 0x81).unwrap();
+        assert_eq!(serial.read8(0xFF02).unwrap(), 0xFF);
+
+        serial.tick(BIT_CYCLES * 7, &mut interrupts);
+        assert_eq!(interrupts.IF, 0);
+        //our bits go out the top as 1s come in the bottom
+        assert_eq!(serial.read8(0xFF01).unwrap(), (b'O' << 7) | 0x7F);
+        serial.tick(BIT_CYCLES, &mut interrupts);
+        assert_eq!(interrupts.IF, Interrupt::Serial.bit());
+        assert_eq!(serial.read8(0xFF02).unwrap(), 0x7F);
+        assert_eq!(serial.read8(0xFF01).unwrap(), 0xFF);
+        assert_eq!(*output.lock().unwrap(), b"O");
+    }
+
+    #[test]
+    fn external_clock_waits_forever_when_disconnected() {
+        let mut serial = Serial::default();
+        let mut interrupts = InterruptController::new();
+        serial.write8(0xFF01, 0x42).unwrap();
+        serial.write8(0xFF02, 0x80).unwrap();
+        serial.tick(BIT_CYCLES * 100, &mut interrupts);
+        assert_eq!(interrupts.IF, 0);
+        assert_eq!(serial.read8(0xFF02).unwrap(), 0xFE);
+        assert_eq!(serial.read8(0xFF01).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn tcp_link_transfer() {
+        let host = TcpLink::host("127.0.0.1:0").unwrap();
+        let addr = host.listener.as_ref().unwrap().local_addr().unwrap();
+        let client = TcpLink::connect(addr).unwrap();
+
+        let mut master = Serial::new(Box::new(client));
+        let mut master_interrupts = InterruptController::new();
+        let mut slave = Serial::new(Box::new(host));
+        let mut slave_interrupts = InterruptController::new();
+
+        slave.write8(0xFF01, 0x42).unwrap();
+        slave.write8(0xFF02, 0x80).unwrap();
+        master.write8(0xFF01, 0x99).unwrap();
+        master.write8(0xFF02, 0x81).unwrap();
+
+        //nothing here blocks, so just run both sides until the reply makes it back
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while master_interrupts.IF == 0 {
+            assert!(Instant::now() < deadline, "transfer never finished");
+            slave.tick(BIT_CYCLES, &mut slave_interrupts);
+            master.tick(BIT_CYCLES, &mut master_interrupts);
+        }
+        assert_eq!(master.read8(0xFF01).unwrap(), 0x42);
+        assert_eq!(slave_interrupts.IF, Interrupt::Serial.bit());
+        assert_eq!(slave.read8(0xFF01).unwrap(), 0x99);
+    }
+}
@@ -1,8 +1,12 @@
+use crate::audio_sink::{AudioSink, NullSink};
+use crate::bus::Bus;
 use crate::cpu::Register16;
 use crate::cpu::Register16::*;
 use crate::cpu::Register8;
 use crate::cpu::Register8::*;
-use crate::{cart::Cart, cpu::Cpu, io::Io, FrontendCmd};
+use crate::interrupt::Interrupt;
+use crate::joypad::Button;
+use crate::{cart::Cart, cpu::Cpu, dma::Dma, io::Io, FrontendCmd};
 use rand::prelude::*;
 use rand::rngs::ThreadRng;
 use std::{
@@ -13,52 +17,9 @@ use std::{
         mpsc::{Receiver, Sender},
         Arc, Mutex,
     },
+    time::{Duration, Instant},
 };
 
-#[rustfmt::skip]
-//opcode timings IN T_CYCLES
-static OPCODE_TIMINGS: [usize; 256] = [
-//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F 
-    0, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x0
-    0, 12, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x1
-    8, 12, 8, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, //0x2
-    8, 12, 0, 0, 0, 0, 12, 0, 8, 0, 0, 0, 0, 0, 0, 0, //0x3
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x4
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x5
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x6
-    8, 8, 8, 8, 8, 8, 0, 8, 0, 0, 0, 0, 0, 0, 0, 0, //0x7
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x8
-    0, 0, 0, 0, 0, 0, 0, 0, 4, 4, 4, 4, 4, 4, 4, 4, //0x9
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xA
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xB
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xC
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xD
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, //0xE
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xF
-];
-
-#[rustfmt::skip]
-//opcode timings IN T_CYCLES
-static CB_OPCODE_TIMINGS: [usize; 256] = [
-//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F 
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x0
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x1
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x2
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x3
-    4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x4
-    4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x5
-    4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x6
-    4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x7
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x8
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0x9
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xA
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xB
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xC
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xD
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, //0xE
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //0xF
-];
-
 //this is just a convinience struct to bundle all of the comms data for backend->frontend comms and vice versa
 //essentially anything that is not actually related to the system's operation
 struct Comms {
@@ -71,18 +32,32 @@ struct Comms {
     command_rx: Receiver<BackendCmd>,
     cpu_tx: Sender<Cpu>,
     repaint_frontend_callback: Box<dyn Fn() + Send>,
+    audio_sink: Box<dyn AudioSink>,
     //mem_tx: Sender<(usize, Vec<u8>)>,
 }
 
+//154 lines of 456 dots. at 4194304 T cycles a second that comes out to ~59.7 frames a second
+const CYCLES_PER_FRAME: usize = 70224;
+const FRAME_DURATION: Duration =
+    Duration::from_nanos(1_000_000_000 * CYCLES_PER_FRAME as u64 / 4_194_304);
+//how many frames behind real time we can fall before we stop trying to catch up
+const MAX_FRAME_LAG: u32 = 4;
+
 #[derive(PartialEq)]
 pub enum BackendCmd {
     Shutdown,
+    //a button was pressed (true) or released (false) on the frontend
+    Input(Button, bool),
 }
 
 enum SystemState {
     Running,
     Crashed,
     BreakpointHit,
+    //HALT: cpu is idle until IE & IF is non zero, then it wakes up (and services it if IME is set)
+    Halted,
+    //STOP: even lower power than HALT, only a joypad press gets us out of this one
+    Stopped,
 }
 
 //this represents our entire GB system, both physical hardware units, as well as frontend communications abstractions
@@ -91,11 +66,18 @@ pub struct System {
     cpu: Cpu,
     cart: Cart,
     io: Io,
+    dma: Dma,
     boot_rom: [u8; 0x100],
     pub vram: [u8; 8192],
     pub wram: [u8; 8192],
-    pub hram: [u8; 126],
+    //sprite attribute table, 40 entries of 4 bytes
+    pub oam: [u8; 160],
+    pub hram: [u8; 127],
     M_cycles: usize,
+    //how many M cycles the step in flight has already ticked through its bus accesses, None outside run_frame
+    step_m_cycles: Option<usize>,
+    //set when the ppu finishes a frame partway through a step
+    frame_done: bool,
     status: SystemState,
 }
 
@@ -123,16 +105,21 @@ impl System {
                 command_rx,
                 cpu_tx,
                 repaint_frontend_callback,
+                audio_sink: Box::new(NullSink),
                 //mem_tx,
             },
             cpu,
             cart,
             io,
+            dma: Dma::new(),
             boot_rom,
             vram: [0; 8192],
             wram: [0; 8192],
-            hram: [0; 126],
+            oam: [0; 160],
+            hram: [0; 127],
             M_cycles: 0,
+            step_m_cycles: None,
+            frame_done: false,
             status: SystemState::Running,
         }
     }
@@ -170,24 +157,28 @@ impl System {
     }*/
 }
 
+//the core only stops to talk to the frontend between frames. everything in here happens once a frame, and the
+//lock is held for that whole frame of emulation, so a frontend command (or the debugger grabbing the lock) can
+//wait up to ~16ms for its turn. it is let go while we sleep off the rest of the frame.
+//audio gets flushed once a frame too, so the live sink gets ~16.7ms chunks against its 100ms LIVE_BUFFER_MS
+//queue, which its rate control keeps about half full
 pub fn run_mutex(system: Arc<Mutex<System>>) {
+    let mut next_frame = Instant::now();
     'running: loop {
         let mut sys = system.lock().unwrap();
         //see if we have gotten any commands from the frontend, and process and parse them if so
-        let recv_cmds = sys.comms.command_rx.try_iter();
+        let recv_cmds: Vec<BackendCmd> = sys.comms.command_rx.try_iter().collect();
         for cmd in recv_cmds {
-            if cmd == BackendCmd::Shutdown {
-                break 'running;
+            match cmd {
+                BackendCmd::Shutdown => break 'running,
+                BackendCmd::Input(button, pressed) => {
+                    let io = &mut sys.io;
+                    io.joypad.set_button(button, pressed, &mut io.interrupts);
+                }
             }
         }
 
-        debug!("PC: {:#04x}", sys.cpu.rf.PC);
-        //fetch the opcode
-        let pc = sys.cpu.rf.PC;
-        let op = sys.read(pc, 1).unwrap()[0];
-
-        //execute the opcode
-        let execution = sys.execute_op(op);
+        let execution = sys.run_frame();
         //break execution loop on execution error and let the frontend know what went wrong
         if execution.is_err() {
             sys.comms
@@ -200,8 +191,241 @@ pub fn run_mutex(system: Arc<Mutex<System>>) {
             break 'running;
         }
 
+        if execution.unwrap() {
+            sys.send_frame();
+        }
+        sys.flush_audio();
+        sys.cart.tick_save();
         sys.comms.cpu_tx.send(sys.cpu.clone()).unwrap();
         drop(sys);
+
+        //sleep off whatever is left of this frame's share of real time. if we fell way behind (a slow frame, the
+        //machine went to sleep) start counting from now instead of racing to catch up
+        next_frame += FRAME_DURATION;
+        let now = Instant::now();
+        if next_frame > now {
+            std::thread::sleep(next_frame - now);
+        } else if now - next_frame > FRAME_DURATION * MAX_FRAME_LAG {
+            next_frame = now;
+        }
+    }
+}
+
+impl System {
+    //puts everything in the state the boot rom leaves it in and starts at the cart entry point, for carts that
+    //the boot rom would refuse to run
+    pub fn skip_boot_rom(&mut self) -> Result<(), ExecutionError> {
+        self.cpu.rf.AF_write(0x01B0);
+        self.cpu.rf.BC_write(0x0013);
+        self.cpu.rf.DE_write(0x00D8);
+        self.cpu.rf.HL_write(0x014D);
+        self.cpu.rf.SP = 0xFFFE;
+        self.cpu.rf.PC = 0x0100;
+
+        self.write8(0xFF0F, 0xE1)?;
+        //lcd on, bg on, tile data at 8000
+        self.write8(0xFF40, 0x91)?;
+        self.write8(0xFF47, 0xFC)?;
+        //and unmap the boot rom
+        self.write8(0xFF50, 0x01)?;
+        Ok(())
+    }
+
+    //runs the cpu until the ppu finishes a frame, or for a frame's worth of cycles if the lcd is off and it never
+    //will. returns whether there is a new frame to show
+    pub fn run_frame(&mut self) -> Result<bool, ExecutionError> {
+        let mut cycles = 0;
+        while cycles < CYCLES_PER_FRAME {
+            debug!("PC: {:#04x}", self.cpu.rf.PC);
+            //service an interrupt or fetch and execute the next opcode
+            let step_cycles = self.scheduled_step()?;
+            cycles += step_cycles;
+            self.M_cycles += step_cycles / 4;
+            if std::mem::take(&mut self.frame_done) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    //runs one step with everything else ticking along as it goes. each bus access the cpu makes is an M cycle,
+    //so the rest of the system is caught up right before it lands, and whatever internal cycles the step took
+    //on top of those get ticked at the end
+    fn scheduled_step(&mut self) -> Result<usize, ExecutionError> {
+        self.step_m_cycles = Some(0);
+        let step = self.step();
+        let ticked = self.step_m_cycles.take().unwrap_or(0);
+        let step_cycles = step?;
+        let remaining = (step_cycles / 4).saturating_sub(ticked);
+        self.frame_done |= self.tick(remaining * 4);
+        Ok(step_cycles)
+    }
+
+    //called on every cpu bus access, ticks one M cycle if we are inside a scheduled step
+    fn tick_bus_access(&mut self) {
+        if let Some(ticked) = self.step_m_cycles {
+            self.step_m_cycles = Some(ticked + 1);
+            self.frame_done |= self.tick_m_cycle();
+        }
+    }
+
+    //catches everything that isnt the cpu up on however many T cycles the cpu just took, one M cycle at a time
+    //so they all see each other in the right order. returns true if the ppu finished a frame
+    pub fn tick(&mut self, cycles: usize) -> bool {
+        let mut frame_done = false;
+        for _ in 0..cycles / 4 {
+            frame_done |= self.tick_m_cycle();
+        }
+        frame_done
+    }
+
+    fn tick_m_cycle(&mut self) -> bool {
+        if let Some((source, offset)) = self.dma.step() {
+            //DMA doesnt go through the cpu's view of the bus, it is the thing locking it
+            self.oam[offset] = self.bus_read8(source).unwrap_or(0xFF);
+        }
+
+        //the divider is stopped along with everything else in STOP
+        if !matches!(self.status, SystemState::Stopped) {
+            self.io.timer.tick(4, &mut self.io.interrupts);
+        }
+        self.io.serial.tick(4, &mut self.io.interrupts);
+        self.io.audio.tick(4);
+
+        self.io.ppu.tick(
+            4,
+            &self.vram,
+            &self.oam,
+            &mut self.io.interrupts,
+            &mut self.comms.screen_data,
+        )
+    }
+
+    //hands the finished frame to the frontend and pokes it to redraw
+    fn send_frame(&mut self) {
+        //the frontend going away just means nobody is watching anymore
+        if self
+            .comms
+            .screen_tx
+            .send(self.comms.screen_data.clone())
+            .is_ok()
+        {
+            (self.comms.repaint_frontend_callback)();
+        }
+    }
+
+    //write out battery backed cart ram, for when we are shutting down
+    pub fn flush_save(&mut self) {
+        self.cart.flush_save();
+    }
+
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.io.audio.set_sample_rate(sink.sample_rate());
+        self.comms.audio_sink = sink;
+    }
+
+    //hands whatever the apu has made to the sink, and lets the sink speed it up or slow it down a touch
+    fn flush_audio(&mut self) {
+        let samples = self.io.audio.take_samples();
+        if samples.is_empty() {
+            return;
+        }
+        let sink = &mut self.comms.audio_sink;
+        sink.push(&samples);
+        let rate = (sink.sample_rate() as f32 * sink.rate_adjustment()).round() as u32;
+        if rate != self.io.audio.sample_rate() {
+            self.io.audio.set_sample_rate(rate);
+        }
+    }
+
+    //push out the last few samples and close the sink, for when we are shutting down
+    pub fn finish_audio(&mut self) {
+        self.flush_audio();
+        self.comms.audio_sink.finish();
+    }
+
+    //runs one "step" of the cpu, which is either dispatching an interrupt or executing a single instruction.
+    //returns the number of T cycles it took
+    pub fn step(&mut self) -> Result<usize, ExecutionError> {
+        //low power states just burn a cycle at a time until something wakes us up
+        match self.status {
+            SystemState::Halted => {
+                //HALT wakes on any enabled pending interrupt, whether or not IME is set
+                if self.io.interrupts.pending() == 0 {
+                    return Ok(4);
+                }
+                self.status = SystemState::Running;
+            }
+            SystemState::Stopped => {
+                //a button going low raises the joypad request line, which is the only thing that ends STOP.
+                //unlike HALT this doesnt care about IE
+                if (self.io.interrupts.IF & Interrupt::Joypad.bit()) == 0 {
+                    return Ok(4);
+                }
+                self.status = SystemState::Running;
+            }
+            _ => {}
+        }
+
+        //interrupt dispatch happens between instructions and takes the place of the next fetch
+        let dispatch_cycles = self.service_interrupts()?;
+        if dispatch_cycles != 0 {
+            return Ok(dispatch_cycles);
+        }
+
+        //if the last instruction was EI, IME gets set once the instruction after it is done
+        let enable_ime = self.cpu.ime_pending;
+
+        //fetch the opcode
+        let pc = self.cpu.rf.PC;
+        let op = self.read8(pc)?;
+
+        //HALT bug: the fetch right after a bugged HALT doesnt increment PC, so the byte after HALT gets read twice.
+        //every handler starts by stepping PC past its opcode, so winding it back by one gets us the same effect
+        if self.cpu.halt_bug {
+            self.cpu.halt_bug = false;
+            self.cpu.rf.PC = pc.wrapping_sub(1);
+        }
+
+        //execute the opcode
+        let cycles = self.execute_op(op)?;
+
+        //the instruction we just ran might have been DI, which cancels the pending enable
+        if enable_ime && self.cpu.ime_pending {
+            self.cpu.ime = true;
+            self.cpu.ime_pending = false;
+        }
+
+        Ok(cycles)
+    }
+
+    //if IME is set and something is pending, push PC and jump to the highest priority vector.
+    //returns the cycles spent dispatching, which is 0 if nothing happened
+    fn service_interrupts(&mut self) -> Result<usize, ExecutionError> {
+        if !self.cpu.ime {
+            return Ok(0);
+        }
+        let int = match self.io.interrupts.highest_pending() {
+            Some(int) => int,
+            None => return Ok(0),
+        };
+
+        self.cpu.ime = false;
+        self.cpu.ime_pending = false;
+        self.io.interrupts.acknowledge(int);
+
+        self.push16(self.cpu.rf.PC)?;
+
+        self.cpu.rf.PC = int.vector();
+
+        debug!(
+            "servicing {} interrupt, jumping to {:#04x}",
+            int,
+            int.vector()
+        );
+
+        //2 wait states, 2 for the push, 1 to load PC
+        Ok(20)
     }
 }
 
@@ -233,9 +457,10 @@ impl std::fmt::Display for ExecutionError {
 impl System {
     pub fn execute_op(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         match opcode {
+            0x00 => self.NOP(opcode),
             //0x31 => self.LDSP(opcode),
             0x01 | 0x11 | 0x21 | 0x31 => self.LD16imm(opcode),
-            0x70..=0x75 | 0x77 | 0x22 | 0x32 | 0x36 => self.STRHL(opcode),
+            0x70..=0x75 | 0x77 | 0x36 => self.STRHL(opcode),
             //0xA8..=0xAF => self.XOR(opcode),
             0x20 | 0x30 | 0x28 | 0x38 | 0x18 => self.JRCond(opcode),
             0x06 | 0x16 | 0x26 | 0x0E | 0x1E | 0x2E | 0x3E => self.LD8imm(opcode),
@@ -243,18 +468,23 @@ impl System {
             0xF0 | 0xF2 => self.ReadIO(opcode),
             0x04 | 0x14 | 0x24 | 0x34 | 0x0C | 0x1c | 0x2c | 0x3C => self.INC8(opcode),
             0x0A | 0x1A | 0x2A | 0x3A => self.LDA(opcode),
-            //TODO decide where you actually want store hl ind to go lol
             0x02 | 0x12 | 0x22 | 0x32 => self.STRA(opcode),
             0xC4 | 0xD4 | 0xCC | 0xDC | 0xCD => self.CALL(opcode),
+            0x76 => self.HALT(opcode),
             0x40..=0x75 | 0x77..=0x7F => self.MV(opcode),
             0xC5 | 0xD5 | 0xE5 | 0xF5 => self.PUSH(opcode),
             0xC1 | 0xD1 | 0xE1 | 0xF1 => self.POP(opcode),
             0x07 => self.RLCA(opcode),
             0x17 => self.RLA(opcode),
+            0x0F => self.RRCA(opcode),
+            0x1F => self.RRA(opcode),
             0x05 | 0x15 | 0x25 | 0x35 | 0x0D | 0x1D | 0x2D | 0x3D => self.DEC8(opcode),
             0x03 | 0x13 | 0x23 | 0x33 => self.INC16(opcode),
             0x0B | 0x1B | 0x2B | 0x3B => self.DEC16(opcode),
+            0x09 | 0x19 | 0x29 | 0x39 => self.ADD16(opcode),
             0xC0 | 0xD0 | 0xC8 | 0xD8 | 0xC9 | 0xD9 => self.RET(opcode),
+            0xC2 | 0xD2 | 0xCA | 0xDA | 0xC3 | 0xE9 => self.JP(opcode),
+            0xC7 | 0xD7 | 0xE7 | 0xF7 | 0xCF | 0xDF | 0xEF | 0xFF => self.RST(opcode),
             0x80..=0x87 | 0xC6 => self.ADD(opcode),
             0x88..=0x8F | 0xCE => self.ADC(opcode),
             0x90..=0x97 | 0xD6 => self.SUB(opcode),
@@ -265,11 +495,22 @@ impl System {
             0xB8..=0xBF | 0xFE => self.CP(opcode),
             0xEA => self.STRA16(opcode),
             0xFA => self.LDA16(opcode),
+            0x08 => self.STRSP16(opcode),
+            0xE8 | 0xF8 => self.ADDSPimm(opcode),
+            0xF9 => self.LDSPHL(opcode),
+            0x27 => self.DAA(opcode),
+            0x2F => self.CPL(opcode),
+            0x37 | 0x3F => self.SCFCCF(opcode),
+            0xF3 | 0xFB => self.DIEI(opcode),
+            0x10 => self.STOP(opcode),
             0xCB => {
                 self.cpu.rf.PC += 1;
-                let second_byte = self.read(self.cpu.rf.PC, 1)?[0];
-                self.execute_CB_op(second_byte)
+                let second_byte = self.read8(self.cpu.rf.PC)?;
+                //4 cycles for the prefix fetch on top of whatever the CB op itself costs
+                Ok(4 + self.execute_CB_op(second_byte)?)
             }
+            //0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD
+            //these are illegal and hard lock the real cpu, so crashing is about as accurate as we can get
             _ => {
                 self.comms
                     .log_tx
@@ -305,15 +546,43 @@ impl System {
     }
 }
 
+//the 8 bit alu ops are 4 cycles for a register operand, and 8 if they have to go to memory for (HL) or an imm8
+fn alu_cycles(opcode: u8) -> usize {
+    if opcode >= 0xC0 || (opcode & 0b0000_0111) == 0x6 {
+        8
+    } else {
+        4
+    }
+}
+
 #[allow(non_snake_case)]
 impl System {
+    //every stack operation (CALL/RET/RST/PUSH/POP and interrupt dispatch) goes through these two.
+    //the stack grows down, and the high byte is always pushed first so the value ends up little endian in memory
+    pub fn push16(&mut self, value: u16) -> Result<(), ExecutionError> {
+        //write	upper->(--SP)
+        self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
+        self.write8(self.cpu.rf.SP, ((value & 0xFF00) >> 8) as u8)?;
+        //write	lower->(--SP)
+        self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
+        self.write8(self.cpu.rf.SP, (value & 0x00FF) as u8)?;
+        Ok(())
+    }
+    pub fn pop16(&mut self) -> Result<u16, ExecutionError> {
+        //read lower<-(SP++)
+        let lower = self.read8(self.cpu.rf.SP)?;
+        self.cpu.rf.SP = self.cpu.rf.SP.wrapping_add(1);
+        //read upper<-(SP++)
+        let upper = self.read8(self.cpu.rf.SP)?;
+        self.cpu.rf.SP = self.cpu.rf.SP.wrapping_add(1);
+        Ok((upper as u16) << 8 | lower as u16)
+    }
+
     pub fn LD16imm(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         //step past the opcode we fetched
         self.cpu.rf.PC += 1;
 
-        let data = self.read(self.cpu.rf.PC, 2)?;
-        //let data: u16 = ((data[0] as u16) << 8) | data[1] as u16;
-        let data: u16 = (data[0] as u16) | ((data[1] as u16) << 8);
+        let data = self.read16(self.cpu.rf.PC)?;
 
         let log = match opcode {
             //BC
@@ -345,7 +614,7 @@ impl System {
         //self.comms.log_tx.send(log).unwrap();
         debug!("{log}");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(12);
     }
 
     pub fn STRHL(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -355,56 +624,44 @@ impl System {
         let log = match opcode {
             //B
             0x70 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.B])?;
+                self.write8(self.cpu.rf.HL_read(), self.cpu.rf.B)?;
                 format!("LD (HL), B")
             }
             //C
             0x71 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.C])?;
+                self.write8(self.cpu.rf.HL_read(), self.cpu.rf.C)?;
                 format!("LD (HL), C")
             }
             //D
             0x72 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.D])?;
+                self.write8(self.cpu.rf.HL_read(), self.cpu.rf.D)?;
                 format!("LD (HL), D")
             }
             //E
             0x73 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.E])?;
+                self.write8(self.cpu.rf.HL_read(), self.cpu.rf.E)?;
                 format!("LD (HL), E")
             }
             //H
             0x74 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.H])?;
+                self.write8(self.cpu.rf.HL_read(), self.cpu.rf.H)?;
                 format!("LD (HL), H")
             }
             //L
             0x75 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.L])?;
+                self.write8(self.cpu.rf.HL_read(), self.cpu.rf.L)?;
                 format!("LD (HL), L")
             }
             //A
             0x77 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.A])?;
+                self.write8(self.cpu.rf.HL_read(), self.cpu.rf.A)?;
                 format!("LD (HL), A")
             }
-            //HL increment
-            0x22 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.A])?;
-                self.cpu.rf.HL_write(self.cpu.rf.HL_read() + 1);
-                format!("LD (HL+), A")
-            }
-            //HL decrement
-            0x32 => {
-                self.write(self.cpu.rf.HL_read(), &[self.cpu.rf.A])?;
-                self.cpu.rf.HL_write(self.cpu.rf.HL_read() - 1);
-                format!("LD (HL-), A")
-            }
             //immeadiate
             0x36 => {
-                let data = self.read(self.cpu.rf.PC, 1)?[0];
+                let data = self.read8(self.cpu.rf.PC)?;
                 self.cpu.rf.PC += 1;
-                self.write(self.cpu.rf.HL_read(), &[data])?;
+                self.write8(self.cpu.rf.HL_read(), data)?;
                 format!("LD (HL), imm8")
             }
             _ => {
@@ -414,7 +671,8 @@ impl System {
         //self.comms.log_tx.send(log).unwrap();
         debug!("{log}");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        //the imm8 variant has an extra fetch
+        return Ok(if opcode == 0x36 { 12 } else { 8 });
     }
 
     pub fn JRCond(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -422,7 +680,7 @@ impl System {
         self.cpu.rf.PC += 1;
 
         //possible offset is
-        let offset: i8 = self.read(self.cpu.rf.PC, 1)?[0] as i8;
+        let offset: i8 = self.read8(self.cpu.rf.PC)? as i8;
         //move past this byte we just fetched
         self.cpu.rf.PC += 1;
 
@@ -454,7 +712,8 @@ impl System {
         //self.comms.log_tx.send(log.to_string()).unwrap();
         debug!("{log}");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize] + if cond { 4 } else { 0 });
+        //taking the jump costs an extra 4 cycles to actually load PC
+        return Ok(if cond { 12 } else { 8 });
     }
 
     pub fn LD8imm(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -466,7 +725,7 @@ impl System {
         let reg: crate::cpu::Register8 = (reg as usize).try_into().unwrap();
 
         //get the imm we are loading
-        let imm8 = self.read(self.cpu.rf.PC, 1)?[0];
+        let imm8 = self.read8(self.cpu.rf.PC)?;
         self.cpu.rf.PC += 1;
 
         //actually do the load
@@ -476,14 +735,14 @@ impl System {
         debug!("{log}");
         //self.comms.log_tx.send(log).unwrap();
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(8);
     }
 
     pub fn WriteIO(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
         let (log, mut address) = if opcode == 0xE0 {
-            let imm = self.read(self.cpu.rf.PC, 1)?[0];
+            let imm = self.read8(self.cpu.rf.PC)?;
             self.cpu.rf.PC += 1;
             ("LD (FF00+u8),A", imm as usize)
         } else {
@@ -491,18 +750,18 @@ impl System {
         };
         address += 0xFF00;
 
-        self.write(address as u16, &[self.cpu.rf[A]])?;
+        self.write8(address as u16, self.cpu.rf[A])?;
 
         debug!("{log}");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(if opcode == 0xE0 { 12 } else { 8 });
     }
     pub fn ReadIO(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
         let (address, log) = if opcode == 0xF0 {
             //imm
-            let offset = self.read(self.cpu.rf.PC, 1)?[0];
+            let offset = self.read8(self.cpu.rf.PC)?;
             self.cpu.rf.PC += 1;
             (
                 0xFF00 + offset as u16,
@@ -515,30 +774,25 @@ impl System {
             )
         };
 
-        self.cpu.rf[A] = self.read(address, 1)?[0];
+        self.cpu.rf[A] = self.read8(address)?;
 
         debug!("{log}");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(if opcode == 0xF0 { 12 } else { 8 });
     }
 
     pub fn INC8(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
         let reg_mask = 0b0011_1000;
-        let reg: usize = ((opcode & reg_mask) >> 3) as usize;
-        let (log, result, original) = if reg == 0b00110_0000 {
+        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
+        let (log, result, original) = if reg == HLInd {
             //HL indirect
-            let cur = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            self.write(self.cpu.rf.HL_read().wrapping_add(1), &[cur])?;
-            (
-                "INC (HL)".to_string(),
-                self.read(self.cpu.rf.HL_read(), 1)?[0],
-                cur,
-            )
+            let cur = self.read8(self.cpu.rf.HL_read())?;
+            self.write8(self.cpu.rf.HL_read(), cur.wrapping_add(1))?;
+            ("INC (HL)".to_string(), cur.wrapping_add(1), cur)
         } else {
             //normal reg increment
-            let reg: Register8 = reg.try_into().unwrap();
             let cur = self.cpu.rf[reg];
             self.cpu.rf[reg] = cur.wrapping_add(1);
             (format!("INC {}", reg), self.cpu.rf[reg], cur)
@@ -547,34 +801,25 @@ impl System {
         //FLAGS!!!!!!!
         self.cpu.rf.z_set(result == 0);
         self.cpu.rf.n_set(false);
-        //HC DEPENDANT ON WHAT???
-        //TODO: I AM LITERALLY JUST GUESSING AT BEHAVIOR HERE WHAT IS IT SUPPOSED TO BE
-        //note 3/14/23: gameboy has no instructions dependant on the HC flag so bad behavior might be fine
-        self.cpu
-            .rf
-            .h_set((result & 0b0001_0000) != 0 && (original & 0b0000_1111) == 0b0000_1111);
+        //half carry is a carry out of bit 3, which for a +1 can only happen if the low nibble was all 1s
+        self.cpu.rf.h_set((original & 0x0F) == 0x0F);
         //carry untouched
 
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(if reg == HLInd { 12 } else { 4 });
     }
     pub fn DEC8(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
         let reg_mask = 0b0011_1000;
-        let reg: usize = ((opcode & reg_mask) >> 3) as usize;
-        let (log, result, original) = if reg == 0b00110_0000 {
+        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
+        let (log, result, original) = if reg == HLInd {
             //HL indirect
-            let cur = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            self.write(self.cpu.rf.HL_read().wrapping_sub(1), &[cur])?;
-            (
-                "DEC (HL)".to_string(),
-                self.read(self.cpu.rf.HL_read(), 1)?[0],
-                cur,
-            )
+            let cur = self.read8(self.cpu.rf.HL_read())?;
+            self.write8(self.cpu.rf.HL_read(), cur.wrapping_sub(1))?;
+            ("DEC (HL)".to_string(), cur.wrapping_sub(1), cur)
         } else {
-            //normal reg increment
-            let reg: Register8 = reg.try_into().unwrap();
+            //normal reg decrement
             let cur = self.cpu.rf[reg];
             self.cpu.rf[reg] = cur.wrapping_sub(1);
             (format!("DEC {}", reg), self.cpu.rf[reg], cur)
@@ -582,17 +827,13 @@ impl System {
 
         //FLAGS!!!!!!!
         self.cpu.rf.z_set(result == 0);
-        self.cpu.rf.n_set(false);
-        //HC DEPENDANT ON WHAT???
-        //TODO: I AM LITERALLY JUST GUESSING AT BEHAVIOR HERE WHAT IS IT SUPPOSED TO BE
-        //note 3/14/23: gameboy has no instructions dependant on the HC flag so bad behavior might be fine
-        self.cpu
-            .rf
-            .h_set((result & 0b0001_0000) != 0 && (original & 0b0000_1111) == 0b0000_1111);
+        self.cpu.rf.n_set(true);
+        //half borrow from bit 4, which for a -1 can only happen if the low nibble was all 0s
+        self.cpu.rf.h_set((original & 0x0F) == 0x00);
         //carry untouched
 
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(if reg == HLInd { 12 } else { 4 });
     }
 
     pub fn INC16(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -619,7 +860,7 @@ impl System {
         };
 
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(8);
     }
     pub fn DEC16(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -645,7 +886,7 @@ impl System {
         };
 
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(8);
     }
 
     pub fn LDA(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -654,21 +895,21 @@ impl System {
         let (log, value) = match opcode {
             0x0A => {
                 //BC
-                ("LD A, (BC)", self.read(self.cpu.rf.BC_read(), 1)?[0])
+                ("LD A, (BC)", self.read8(self.cpu.rf.BC_read())?)
             }
             0x1A => {
                 //DE
-                ("LD A, (DE)", self.read(self.cpu.rf.DE_read(), 1)?[0])
+                ("LD A, (DE)", self.read8(self.cpu.rf.DE_read())?)
             }
             0x2A => {
                 //HL+
-                let ret = ("LD A, (HL+)", self.read(self.cpu.rf.HL_read(), 1)?[0]);
+                let ret = ("LD A, (HL+)", self.read8(self.cpu.rf.HL_read())?);
                 self.cpu.rf.HL_write(self.cpu.rf.HL_read().wrapping_add(1));
                 ret
             }
             0x3a => {
                 //HL-
-                let ret = ("LD A, (HL-)", self.read(self.cpu.rf.HL_read(), 1)?[0]);
+                let ret = ("LD A, (HL-)", self.read8(self.cpu.rf.HL_read())?);
                 self.cpu.rf.HL_write(self.cpu.rf.HL_read().wrapping_sub(1));
                 ret
             }
@@ -678,7 +919,7 @@ impl System {
         self.cpu.rf.A = value;
 
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(8);
     }
 
     pub fn STRA(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -708,108 +949,37 @@ impl System {
             _ => unreachable!("panicking in LDA on unreachable opcode"),
         };
 
-        self.write(address, &[self.cpu.rf[A]])?;
+        self.write8(address, self.cpu.rf[A])?;
 
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(8);
     }
 
     pub fn CALL(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let address_lower = self.read(self.cpu.rf.PC, 1)?[0];
-        let address_higher = self.read(self.cpu.rf.PC + 1, 1)?[0];
-        let address = ((address_higher as u16) << 8) | address_lower as u16;
+        let address = self.read16(self.cpu.rf.PC)?;
         self.cpu.rf.PC += 2;
 
         //CD imm unconditional
-        let log = match opcode {
-            //NZ
-            0xC4 => {
-                //PC = address
-                if !self.cpu.rf.c_get() {
-                    //write	PC:upper->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[((self.cpu.rf.PC & 0xF0) >> 4) as u8])?;
-                    //write	PC:lower->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[(self.cpu.rf.PC & 0x0F) as u8])?;
-                    self.cpu.rf.PC = address;
-                }
-                "CALL NZ, u16".to_string()
-            }
-            //NC
-            0xD4 => {
-                //PC = address
-                if !self.cpu.rf.c_get() {
-                    //write	PC:upper->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[((self.cpu.rf.PC & 0xF0) >> 4) as u8])?;
-                    //write	PC:lower->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[(self.cpu.rf.PC & 0x0F) as u8])?;
-                    self.cpu.rf.PC = address;
-                }
-                "CALL NC, u16".to_string()
-            }
-            //Z
-            0xCC => {
-                //PC = address
-                if self.cpu.rf.z_get() {
-                    //write	PC:upper->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[((self.cpu.rf.PC & 0xF0) >> 4) as u8])?;
-                    //write	PC:lower->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[(self.cpu.rf.PC & 0x0F) as u8])?;
-                    self.cpu.rf.PC = address;
-                }
-                "CALL Z, u16".to_string()
-            }
-            //C
-            0xDC => {
-                //PC = address
-                if self.cpu.rf.c_get() {
-                    //write	PC:upper->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[((self.cpu.rf.PC & 0xF0) >> 4) as u8])?;
-                    //write	PC:lower->(--SP)
-                    self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                    self.write(self.cpu.rf.SP, &[(self.cpu.rf.PC & 0x0F) as u8])?;
-                    self.cpu.rf.PC = address;
-                }
-                "CALL C, u16".to_string()
-            }
-            //uncond
-            0xCD => {
-                /*let address_lower = self.read(self.cpu.rf.PC, 1)?[0];
-                let address_higher = self.read(self.cpu.rf.PC + 1, 1)?[0];
-                let address: u16 = ((address_higher as u16) << 8) | (address_lower as u16);
-                self.cpu.rf.PC += 2;*/
-
-                debug!(
-                    "high byte: {:#x}, low byte: {:#x}, full addr: {:#x}",
-                    (address_higher as u16) << 8,
-                    address_lower as u16,
-                    address
-                );
-
-                //write	PC:upper->(--SP)
-                self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                self.write(self.cpu.rf.SP, &[((self.cpu.rf.PC & 0xFF00) >> 8) as u8])?;
-                //write	PC:lower->(--SP)
-                self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-                self.write(self.cpu.rf.SP, &[(self.cpu.rf.PC & 0xFF) as u8])?;
-
-                //PC = address
-                self.cpu.rf.PC = address;
-                format!("CALL u16 {:#x}", address)
-            }
+        let (log, taken) = match opcode {
+            0xC4 => ("CALL NZ, u16", !self.cpu.rf.z_get()),
+            0xD4 => ("CALL NC, u16", !self.cpu.rf.c_get()),
+            0xCC => ("CALL Z, u16", self.cpu.rf.z_get()),
+            0xDC => ("CALL C, u16", self.cpu.rf.c_get()),
+            0xCD => ("CALL u16", true),
             _ => unreachable!("crashing in CALL on bad opcode"),
         };
 
-        debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        if taken {
+            //return address is the instruction after this one, which PC is already pointing at
+            self.push16(self.cpu.rf.PC)?;
+            self.cpu.rf.PC = address;
+        }
+
+        debug!("{log} {:#x}", address);
+        //taking the call costs an extra 12 cycles for the push and the jump
+        return Ok(if taken { 24 } else { 12 });
     }
 
     pub fn MV(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -823,65 +993,88 @@ impl System {
         let log = format!("LD {}, {}", dst, src);
 
         if dst == HLInd {
-            self.write(self.cpu.rf.HL_read(), &[self.cpu.rf[src]])?;
+            self.write8(self.cpu.rf.HL_read(), self.cpu.rf[src])?;
         } else if src == HLInd {
-            self.cpu.rf[dst] = self.read(self.cpu.rf.HL_read(), 1)?[0];
+            self.cpu.rf[dst] = self.read8(self.cpu.rf.HL_read())?;
         } else {
             self.cpu.rf[dst] = self.cpu.rf[src];
         }
 
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(if dst == HLInd || src == HLInd { 8 } else { 4 });
     }
 
     pub fn PUSH(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_pair: (Register8, Register8) = if opcode == 0xC5 {
-            (B, C)
-        } else if opcode == 0xD5 {
-            (D, E)
-        } else if opcode == 0xE5 {
-            (H, L)
-        } else
-        //if opcode == 0xF5 {
-        {
-            (A, F)
+        let (log, reg_pair) = match opcode {
+            0xC5 => ("PUSH BC", BC),
+            0xD5 => ("PUSH DE", DE),
+            0xE5 => ("PUSH HL", HL),
+            0xF5 => ("PUSH AF", AF),
+            _ => unreachable!("panicking in PUSH on an unreachable opcode"),
         };
 
-        self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-        self.write(self.cpu.rf.SP, &[self.cpu.rf[reg_pair.0]])?;
-        self.cpu.rf.SP = self.cpu.rf.SP.wrapping_sub(1);
-        self.write(self.cpu.rf.SP, &[self.cpu.rf[reg_pair.1]])?;
+        self.push16(self.cpu.rf.rf_read16(reg_pair))?;
 
-        let log = format!("PUSH {}{}", reg_pair.0, reg_pair.1);
         debug!("{log}");
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(16);
     }
-
     pub fn POP(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_pair: (Register8, Register8) = if opcode == 0xC5 {
-            (B, C)
-        } else if opcode == 0xD5 {
-            (D, E)
-        } else if opcode == 0xE5 {
-            (H, L)
-        } else
-        //if opcode == 0xF5 {
-        {
-            (A, F)
+        let (log, reg_pair) = match opcode {
+            0xC1 => ("POP BC", BC),
+            0xD1 => ("POP DE", DE),
+            0xE1 => ("POP HL", HL),
+            0xF1 => ("POP AF", AF),
+            _ => unreachable!("panicking in POP on an unreachable opcode"),
         };
 
-        self.cpu.rf[reg_pair.1] = self.read(self.cpu.rf.SP, 1)?[0];
-        self.cpu.rf[reg_pair.0] = self.read(self.cpu.rf.SP, 1)?[0];
-        self.cpu.rf.SP = self.cpu.rf.SP.wrapping_add(2);
+        let mut value = self.pop16()?;
+        if opcode == 0xF1 {
+            //the low nibble of F doesnt physically exist, so it always reads back as 0
+            value &= 0xFFF0;
+        }
+        self.cpu.rf.rf_write16(reg_pair, value);
 
-        let log = format!("POP {}{}", reg_pair.1, reg_pair.0);
         debug!("{log}");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(12);
+    }
+
+    //all of the CB ops encode their target in the low 3 bits, which might be (HL)
+    //these two just save every CB handler from having to special case memory vs register
+    fn cb_target_read(&mut self, reg: Register8) -> Result<u8, ExecutionError> {
+        if reg == HLInd {
+            Ok(self.read8(self.cpu.rf.HL_read())?)
+        } else {
+            Ok(self.cpu.rf[reg])
+        }
+    }
+    fn cb_target_write(&mut self, reg: Register8, value: u8) -> Result<(), ExecutionError> {
+        if reg == HLInd {
+            self.write8(self.cpu.rf.HL_read(), value)?;
+        } else {
+            self.cpu.rf[reg] = value;
+        }
+        Ok(())
+    }
+    //shared tail of all the rotates/shifts: store the result and set Z/N/H/C
+    fn cb_shift_finish(
+        &mut self,
+        reg: Register8,
+        value: u8,
+        carry: bool,
+    ) -> Result<usize, ExecutionError> {
+        self.cb_target_write(reg, value)?;
+        self.cpu.rf.z_set(value == 0);
+        self.cpu.rf.n_set(false);
+        self.cpu.rf.h_set(false);
+        self.cpu.rf.c_set(carry);
+
+        //(HL) variants have to do a read and a write back to memory
+        Ok(if reg == HLInd { 12 } else { 4 })
     }
 
     pub fn RLC(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
@@ -890,23 +1083,14 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let init_val = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            self.cpu.rf.c_set((init_val & 0b1000_0000) != 0);
-            let new_val = init_val.rotate_left(1);
-            self.cpu.rf.z_set(new_val == 0);
-            self.write(self.cpu.rf.HL_read(), &[new_val])?;
-        } else {
-            self.cpu.rf.c_set((self.cpu.rf[reg] & 0b1000_0000) != 0);
-            self.cpu.rf[reg] = self.cpu.rf[reg].rotate_left(1);
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+        let init_val = self.cb_target_read(reg)?;
+        let cycles =
+            self.cb_shift_finish(reg, init_val.rotate_left(1), (init_val & 0b1000_0000) != 0)?;
 
         let log = format!("RLC {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn RRC(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -914,23 +1098,14 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let init_val = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            self.cpu.rf.c_set((init_val & 0b0000_0001) != 0);
-            let new_val = init_val.rotate_right(1);
-            self.cpu.rf.z_set(new_val == 0);
-            self.write(self.cpu.rf.HL_read(), &[new_val])?;
-        } else {
-            self.cpu.rf.c_set((self.cpu.rf[reg] & 0b0000_0001) != 0);
-            self.cpu.rf[reg] = self.cpu.rf[reg].rotate_right(1);
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+        let init_val = self.cb_target_read(reg)?;
+        let cycles =
+            self.cb_shift_finish(reg, init_val.rotate_right(1), (init_val & 0b0000_0001) != 0)?;
 
         let log = format!("RRC {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn RL(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -938,23 +1113,18 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let mut initial_value = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            initial_value <<= 1;
-            initial_value |= if self.cpu.rf.c_get() { 0x1 } else { 0x0 };
-            self.cpu.rf.z_set(initial_value == 0);
-            self.write(self.cpu.rf.HL_read(), &[initial_value])?;
-        } else {
-            self.cpu.rf[reg] <<= 1;
-            self.cpu.rf[reg] |= if self.cpu.rf.c_get() { 0x1 } else { 0x0 };
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+        let init_val = self.cb_target_read(reg)?;
+        let carry_in = if self.cpu.rf.c_get() { 0x1 } else { 0x0 };
+        let cycles = self.cb_shift_finish(
+            reg,
+            (init_val << 1) | carry_in,
+            (init_val & 0b1000_0000) != 0,
+        )?;
 
         let log = format!("RL {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn RR(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -962,30 +1132,22 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let mut initial_value = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            initial_value >>= 1;
-            initial_value |= if self.cpu.rf.c_get() {
-                0b1000_0000
-            } else {
-                0x0
-            };
-            self.cpu.rf.z_set(initial_value == 0);
+        let init_val = self.cb_target_read(reg)?;
+        let carry_in = if self.cpu.rf.c_get() {
+            0b1000_0000
         } else {
-            self.cpu.rf[reg] <<= 1;
-            self.cpu.rf[reg] |= if self.cpu.rf.c_get() {
-                0b1000_0000
-            } else {
-                0x0
-            };
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+            0x0
+        };
+        let cycles = self.cb_shift_finish(
+            reg,
+            (init_val >> 1) | carry_in,
+            (init_val & 0b0000_0001) != 0,
+        )?;
 
         let log = format!("RR {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn SLA(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -993,23 +1155,13 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let init_val = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            self.cpu.rf.c_set((init_val & 0b1000_0000) != 0);
-            let new_val = init_val << 1;
-            self.cpu.rf.z_set(new_val == 0);
-            self.write(self.cpu.rf.HL_read(), &[new_val])?;
-        } else {
-            self.cpu.rf.c_set((self.cpu.rf[reg] & 0b1000_0000) != 0);
-            self.cpu.rf[reg] <<= 0;
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+        let init_val = self.cb_target_read(reg)?;
+        let cycles = self.cb_shift_finish(reg, init_val << 1, (init_val & 0b1000_0000) != 0)?;
 
         let log = format!("SLA {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn SRA(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -1017,23 +1169,18 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let init_val = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            self.cpu.rf.c_set((init_val & 0b1000_0000) != 0);
-            let new_val = (init_val as i8 >> 1) as u8;
-            self.cpu.rf.z_set(new_val == 0);
-            self.write(self.cpu.rf.HL_read(), &[new_val])?;
-        } else {
-            self.cpu.rf.c_set((self.cpu.rf[reg] & 0b1000_0000) != 0);
-            self.cpu.rf[reg] = ((self.cpu.rf[reg] as i8) >> 1) as u8;
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+        //arithmetic shift, so bit 7 stays where it is
+        let init_val = self.cb_target_read(reg)?;
+        let cycles = self.cb_shift_finish(
+            reg,
+            ((init_val as i8) >> 1) as u8,
+            (init_val & 0b0000_0001) != 0,
+        )?;
 
         let log = format!("SRA {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn SWAP(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -1041,21 +1188,14 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let init_val = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            let new_val = (init_val & 0x0F) << 4 | (init_val & 0xF0) >> 4;
-            self.cpu.rf.z_set(new_val == 0);
-            self.write(self.cpu.rf.HL_read(), &[new_val])?;
-        } else {
-            self.cpu.rf[reg] = (self.cpu.rf[reg] & 0x0f) << 4 | (self.cpu.rf[reg] & 0xf0) >> 4;
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+        let init_val = self.cb_target_read(reg)?;
+        let cycles =
+            self.cb_shift_finish(reg, (init_val & 0x0F) << 4 | (init_val & 0xF0) >> 4, false)?;
 
         let log = format!("SWAP {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn SRL(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
@@ -1063,23 +1203,13 @@ impl System {
         let reg_mask = 0b0000_0111;
         let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
 
-        if reg == HLInd {
-            //we are operating in memory
-            let init_val = self.read(self.cpu.rf.HL_read(), 1)?[0];
-            self.cpu.rf.c_set((init_val & 0b1000_0000) != 0);
-            let new_val = init_val >> 1;
-            self.cpu.rf.z_set(new_val == 0);
-            self.write(self.cpu.rf.HL_read(), &[new_val])?;
-        } else {
-            self.cpu.rf.c_set((self.cpu.rf[reg] & 0b1000_0000) != 0);
-            self.cpu.rf[reg] = self.cpu.rf[reg] >> 1;
-            self.cpu.rf.z_set(self.cpu.rf[reg] == 0);
-        }
+        let init_val = self.cb_target_read(reg)?;
+        let cycles = self.cb_shift_finish(reg, init_val >> 1, (init_val & 0b0000_0001) != 0)?;
 
         let log = format!("SRL {}", reg);
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(cycles);
     }
     pub fn RES(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         //step past the op we just fetched
@@ -1088,19 +1218,18 @@ impl System {
         //Shoutout Jarrett for noticing this neat encoding pattern
 
         let testing_bit = (opcode & 0b0011_1000) >> 3;
-        //println!("testing bit {}", testing_bit);
-        let bit_mask = 2_u32.pow((testing_bit) as u32);
-        //println!("bitmask: {:b}", bit_mask);
+        let bit_mask: u8 = 1 << testing_bit;
         let reg = opcode & 0b0000_0111;
         let reg: crate::cpu::Register8 = (reg as usize).try_into().unwrap();
 
         let log = format!("RES {},{}", testing_bit, reg);
 
-        self.cpu.rf[reg] &= !(<u32 as TryInto<u8>>::try_into(bit_mask).unwrap());
+        let value = self.cb_target_read(reg)?;
+        self.cb_target_write(reg, value & !bit_mask)?;
 
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(if reg == HLInd { 12 } else { 4 });
     }
     pub fn SET(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         //step past the op we just fetched
@@ -1109,19 +1238,18 @@ impl System {
         //Shoutout Jarrett for noticing this neat encoding pattern
 
         let testing_bit = (opcode & 0b0011_1000) >> 3;
-        //println!("testing bit {}", testing_bit);
-        let bit_mask = 2_u32.pow((testing_bit) as u32);
-        //println!("bitmask: {:b}", bit_mask);
+        let bit_mask: u8 = 1 << testing_bit;
         let reg = opcode & 0b0000_0111;
         let reg: crate::cpu::Register8 = (reg as usize).try_into().unwrap();
 
-        let log = format!("RES {},{}", testing_bit, reg);
+        let log = format!("SET {},{}", testing_bit, reg);
 
-        self.cpu.rf[reg] |= <u32 as TryInto<u8>>::try_into(bit_mask).unwrap();
+        let value = self.cb_target_read(reg)?;
+        self.cb_target_write(reg, value | bit_mask)?;
 
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(if reg == HLInd { 12 } else { 4 });
     }
     pub fn BIT(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         //step past the op we just fetched
@@ -1130,20 +1258,13 @@ impl System {
         //Shoutout Jarrett for noticing this neat encoding pattern
 
         let testing_bit = (opcode & 0b0011_1000) >> 3;
-        //println!("testing bit {}", testing_bit);
-        let bit_mask = 2_u32.pow((testing_bit) as u32);
-        //println!("bitmask: {:b}", bit_mask);
+        let bit_mask: u8 = 1 << testing_bit;
         let reg = opcode & 0b0000_0111;
         let reg: crate::cpu::Register8 = (reg as usize).try_into().unwrap();
-        /*println!(
-            "register: {} with value {:x}  {:b}",
-            reg, self.cpu.rf[reg], self.cpu.rf[reg]
-        );*/
 
         let log = format!("BIT {},{}", testing_bit, reg);
 
-        let result = (self.cpu.rf[reg] as u32 & bit_mask) >> testing_bit == 1;
-        //println!("result says {}", result);
+        let result = (self.cb_target_read(reg)? & bit_mask) != 0;
 
         self.cpu.rf.z_set(!result);
         self.cpu.rf.n_set(false);
@@ -1153,10 +1274,11 @@ impl System {
         // self.comms.log_tx.send(log).unwrap();
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        //BIT only reads (HL), it never writes it back, so its cheaper than RES/SET
+        return Ok(if reg == HLInd { 8 } else { 4 });
     }
 
-    pub fn RLCA(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+    pub fn RLCA(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
         self.cpu.rf.c_set((self.cpu.rf[A] & 0b1000_0000) != 0);
@@ -1168,181 +1290,237 @@ impl System {
         let log = format!("RLCA");
         debug!("{log}");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(4);
     }
 
-    pub fn RLA(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+    pub fn RLA(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        self.cpu.rf[A] <<= 1;
-        self.cpu.rf[A] |= if self.cpu.rf.c_get() { 0x1 } else { 0x0 };
-        self.cpu.rf.z_set(self.cpu.rf[A] == 0);
+        let carry_in = if self.cpu.rf.c_get() { 0x1 } else { 0x0 };
+        self.cpu.rf.c_set((self.cpu.rf[A] & 0b1000_0000) != 0);
+        self.cpu.rf[A] = (self.cpu.rf[A] << 1) | carry_in;
+        self.cpu.rf.z_set(false);
+        self.cpu.rf.h_set(false);
+        self.cpu.rf.n_set(false);
 
-        let log = format!("RL A");
+        let log = format!("RLA");
         debug!("{log}");
 
-        return Ok(CB_OPCODE_TIMINGS[opcode as usize]);
+        return Ok(4);
     }
 
-    pub fn RET(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+    pub fn RRCA(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let possible_low = self.read(self.cpu.rf.SP, 1)?[0];
-        let possible_high = self.read(self.cpu.rf.SP + 1, 1)?[0];
-        let possible_addr = (possible_high as u16) << 8 | possible_low as u16;
-        debug!(
-            "possible_low: {:#02x}, possible high: {:#02x}, possible addr: {:#04x}",
-            possible_low, possible_high, possible_addr
-        );
+        self.cpu.rf.c_set((self.cpu.rf[A] & 0b0000_0001) != 0);
+        self.cpu.rf[A] = self.cpu.rf[A].rotate_right(1);
+        self.cpu.rf.z_set(false);
+        self.cpu.rf.h_set(false);
+        self.cpu.rf.n_set(false);
 
-        let (log, take) = if opcode == 0xC0 {
-            ("RET NZ", !self.cpu.rf.z_get())
-        } else if opcode == 0xD0 {
-            ("RET NC", !self.cpu.rf.c_get())
-        } else if opcode == 0xC8 {
-            ("RET Z", self.cpu.rf.z_get())
-        } else if opcode == 0xD8 {
-            ("RET C", self.cpu.rf.c_get())
-        } else if opcode == 0xC9 {
-            //TODO: RETI stuff here
-            ("RET", true)
+        let log = format!("RRCA");
+        debug!("{log}");
+
+        return Ok(4);
+    }
+
+    pub fn RRA(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let carry_in = if self.cpu.rf.c_get() {
+            0b1000_0000
         } else {
-            ("RET", true)
+            0x0
+        };
+        self.cpu.rf.c_set((self.cpu.rf[A] & 0b0000_0001) != 0);
+        self.cpu.rf[A] = (self.cpu.rf[A] >> 1) | carry_in;
+        self.cpu.rf.z_set(false);
+        self.cpu.rf.h_set(false);
+        self.cpu.rf.n_set(false);
+
+        let log = format!("RRA");
+        debug!("{log}");
+
+        return Ok(4);
+    }
+
+    pub fn RET(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let (log, take) = match opcode {
+            0xC0 => ("RET NZ", !self.cpu.rf.z_get()),
+            0xD0 => ("RET NC", !self.cpu.rf.c_get()),
+            0xC8 => ("RET Z", self.cpu.rf.z_get()),
+            0xD8 => ("RET C", self.cpu.rf.c_get()),
+            0xC9 => ("RET", true),
+            0xD9 => {
+                //RETI turns interrupts back on straight away (no delay like EI has)
+                self.cpu.ime = true;
+                ("RETI", true)
+            }
+            _ => unreachable!("panicking in RET on an unreachable opcode"),
         };
 
         if take {
-            self.cpu.rf.PC = possible_addr;
-            self.cpu.rf.SP = self.cpu.rf.SP.wrapping_add(2);
+            self.cpu.rf.PC = self.pop16()?;
         }
 
-        debug!("{log} {:#4x}", possible_addr);
+        debug!("{log} {:#4x}", self.cpu.rf.PC);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        //unconditional rets dont have to check a flag, so they are cheaper than a taken conditional one
+        let cycles = match (opcode, take) {
+            (0xC9 | 0xD9, _) => 16,
+            (_, true) => 20,
+            (_, false) => 8,
+        };
+        return Ok(cycles);
     }
 
-    pub fn ADD(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+    pub fn JP(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_mask = 0b0011_1000;
-        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
+        //JP HL doesnt have an immeadiate, its just PC = HL
+        if opcode == 0xE9 {
+            self.cpu.rf.PC = self.cpu.rf.HL_read();
+            debug!("JP HL");
+            return Ok(4);
+        }
 
-        let val = if reg == HLInd {
-            self.read(self.cpu.rf.HL_read(), 1)?[0]
-        } else if opcode & 0xF == 0x6 {
-            let v = self.read(self.cpu.rf.PC, 1)?[0];
+        let address = self.read16(self.cpu.rf.PC)?;
+        self.cpu.rf.PC += 2;
+
+        let (log, cond) = match opcode {
+            0xC2 => ("JP NZ,u16", !self.cpu.rf.z_get()),
+            0xD2 => ("JP NC,u16", !self.cpu.rf.c_get()),
+            0xCA => ("JP Z,u16", self.cpu.rf.z_get()),
+            0xDA => ("JP C,u16", self.cpu.rf.c_get()),
+            0xC3 => ("JP u16", true),
+            _ => unreachable!("panicking in JP on an unreachable opcode"),
+        };
+
+        if cond {
+            self.cpu.rf.PC = address;
+        }
+
+        debug!("{log} {:#04x}", address);
+
+        //taking the jump costs an extra 4 cycles to actually load PC
+        return Ok(if cond { 16 } else { 12 });
+    }
+
+    pub fn RST(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        //the target vector is encoded right in the opcode, 0x00, 0x08, ... 0x38
+        let vector = (opcode & 0b0011_1000) as u16;
+
+        self.push16(self.cpu.rf.PC)?;
+
+        self.cpu.rf.PC = vector;
+
+        debug!("RST {:#02x}", vector);
+
+        return Ok(16);
+    }
+
+    //fetches the second operand for all of the 8 bit alu ops (ADD/ADC/SUB/SBC/AND/XOR/OR/CP).
+    //the 0x80..=0xBF block encodes a register in the low 3 bits, the 0xC6/0xCE/.. column is an imm8
+    fn alu_operand(&mut self, opcode: u8) -> Result<(u8, String), ExecutionError> {
+        if opcode >= 0xC0 {
+            let v = self.read8(self.cpu.rf.PC)?;
             self.cpu.rf.PC += 1;
-            v
+            return Ok((v, format!("{:#02x}", v)));
+        }
+
+        let reg_mask = 0b0000_0111;
+        let reg: Register8 = ((opcode & reg_mask) as usize).try_into().unwrap();
+        let val = if reg == HLInd {
+            self.read8(self.cpu.rf.HL_read())?
         } else {
             self.cpu.rf[reg]
         };
 
-        let result = self.cpu.rf[A].wrapping_add(val);
+        Ok((val, format!("{}", reg)))
+    }
+
+    pub fn ADD(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let (val, operand) = self.alu_operand(opcode)?;
+
+        let original = self.cpu.rf[A];
+        let (result, carry) = original.overflowing_add(val);
         self.cpu.rf[A] = result;
         self.cpu.rf.z_set(result == 0);
         self.cpu.rf.n_set(false);
-        //self.cpu.rf.h_set(val);
-        //self.cpu.rf.c_set(self.cpu.rf.c_get());
+        self.cpu.rf.h_set((original & 0x0F) + (val & 0x0F) > 0x0F);
+        self.cpu.rf.c_set(carry);
 
-        debug!("ADD A, {}", reg);
+        debug!("ADD A, {}", operand);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
     pub fn ADC(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_mask = 0b0011_1000;
-        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
+        let (val, operand) = self.alu_operand(opcode)?;
 
-        let val = if reg == HLInd {
-            self.read(self.cpu.rf.HL_read(), 1)?[0]
-        } else if opcode & 0xF == 0xE {
-            let v = self.read(self.cpu.rf.PC, 1)?[0];
-            self.cpu.rf.PC += 1;
-            v
-        } else {
-            self.cpu.rf[reg]
-        };
-
-        let (result, carry) = self.cpu.rf[A].carrying_add(val, self.cpu.rf.c_get());
+        let original = self.cpu.rf[A];
+        let carry_in = self.cpu.rf.c_get();
+        let (result, carry) = original.carrying_add(val, carry_in);
         self.cpu.rf[A] = result;
         self.cpu.rf.z_set(result == 0);
         self.cpu.rf.n_set(false);
-        //self.cpu.rf.h_set(val);
+        self.cpu
+            .rf
+            .h_set((original & 0x0F) + (val & 0x0F) + carry_in as u8 > 0x0F);
         self.cpu.rf.c_set(carry);
 
-        debug!("ADC A, {}", reg);
+        debug!("ADC A, {}", operand);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
     pub fn SUB(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_mask = 0b0011_1000;
-        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
+        let (val, operand) = self.alu_operand(opcode)?;
 
-        let val = if reg == HLInd {
-            self.read(self.cpu.rf.HL_read(), 1)?[0]
-        } else if opcode & 0xF == 0x6 {
-            let v = self.read(self.cpu.rf.PC, 1)?[0];
-            self.cpu.rf.PC += 1;
-            v
-        } else {
-            self.cpu.rf[reg]
-        };
-
-        let result = self.cpu.rf[A].wrapping_sub(val);
+        let original = self.cpu.rf[A];
+        let (result, borrow) = original.overflowing_sub(val);
         self.cpu.rf[A] = result;
         self.cpu.rf.z_set(result == 0);
         self.cpu.rf.n_set(true);
-        //self.cpu.rf.h_set(val);
-        //self.cpu.rf.c_set(self.cpu.rf.c_get());
+        self.cpu.rf.h_set((original & 0x0F) < (val & 0x0F));
+        self.cpu.rf.c_set(borrow);
 
-        debug!("SUB {}", reg);
+        debug!("SUB {}", operand);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
     pub fn SBC(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_mask = 0b0011_1000;
-        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
-
-        let val = if reg == HLInd {
-            self.read(self.cpu.rf.HL_read(), 1)?[0]
-        } else if opcode & 0xF == 0xE {
-            let v = self.read(self.cpu.rf.PC, 1)?[0];
-            self.cpu.rf.PC += 1;
-            v
-        } else {
-            self.cpu.rf[reg]
-        };
+        let (val, operand) = self.alu_operand(opcode)?;
 
-        let (result, carry) = self.cpu.rf[A].borrowing_sub(val, self.cpu.rf.c_get());
+        let original = self.cpu.rf[A];
+        let carry_in = self.cpu.rf.c_get();
+        let (result, carry) = original.borrowing_sub(val, carry_in);
         self.cpu.rf[A] = result;
         self.cpu.rf.z_set(result == 0);
         self.cpu.rf.n_set(true);
-        //self.cpu.rf.h_set(val);
+        self.cpu
+            .rf
+            .h_set((original & 0x0F) < (val & 0x0F) + carry_in as u8);
         self.cpu.rf.c_set(carry);
 
-        debug!("SBC A, {}", reg);
+        debug!("SBC A, {}", operand);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
     pub fn AND(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_mask = 0b0011_1000;
-        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
-
-        let val = if reg == HLInd {
-            self.read(self.cpu.rf.HL_read(), 1)?[0]
-        } else if opcode & 0xF == 0x6 {
-            let v = self.read(self.cpu.rf.PC, 1)?[0];
-            self.cpu.rf.PC += 1;
-            v
-        } else {
-            self.cpu.rf[reg]
-        };
+        let (val, operand) = self.alu_operand(opcode)?;
 
         let result = self.cpu.rf[A] & val;
         self.cpu.rf[A] = result;
@@ -1352,9 +1530,9 @@ impl System {
         self.cpu.rf.h_set(true);
         self.cpu.rf.c_set(false);
 
-        debug!("AND {}", reg);
+        debug!("AND {}", operand);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
     pub fn XOR(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         //step past the opcode we fetched
@@ -1375,7 +1553,7 @@ impl System {
             0xAD => ("XOR L".to_owned(), self.cpu.rf.A ^ self.cpu.rf.L),
             //(HL)
             0xAE => {
-                let data = self.read(self.cpu.rf.HL_read(), 1)?[0];
+                let data = self.read8(self.cpu.rf.HL_read())?;
 
                 ("XOR (HL)".to_owned(), self.cpu.rf.A ^ data)
             }
@@ -1383,7 +1561,7 @@ impl System {
             0xAF => ("XOR A".to_owned(), self.cpu.rf.A ^ self.cpu.rf.A),
             //imm
             0xEE => {
-                let data = self.read(self.cpu.rf.PC, 1)?[0];
+                let data = self.read8(self.cpu.rf.PC)?;
                 //dont forget to step another byte since we are using an immeadiate
                 self.cpu.rf.PC += 1;
                 (format!("XOR d8 [{:x}]", data), self.cpu.rf.A ^ data)
@@ -1402,25 +1580,14 @@ impl System {
         self.cpu.rf.h_set(false);
         self.cpu.rf.c_set(false);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
     pub fn OR(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_mask = 0b0011_1000;
-        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
-
-        let val = if reg == HLInd {
-            self.read(self.cpu.rf.HL_read(), 1)?[0]
-        } else if opcode & 0xF == 0x6 {
-            let v = self.read(self.cpu.rf.PC, 1)?[0];
-            self.cpu.rf.PC += 1;
-            v
-        } else {
-            self.cpu.rf[reg]
-        };
+        let (val, operand) = self.alu_operand(opcode)?;
 
-        let result = self.cpu.rf[A] & val;
+        let result = self.cpu.rf[A] | val;
         self.cpu.rf[A] = result;
 
         self.cpu.rf.z_set(result == 0);
@@ -1428,65 +1595,259 @@ impl System {
         self.cpu.rf.h_set(false);
         self.cpu.rf.c_set(false);
 
-        debug!("OR {}", reg);
+        debug!("OR {}", operand);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
     pub fn CP(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let reg_mask = 0b0011_1000;
-        let reg: Register8 = (((opcode & reg_mask) >> 3) as usize).try_into().unwrap();
-
-        let val = if reg == HLInd {
-            self.read(self.cpu.rf.HL_read(), 1)?[0]
-        } else if opcode & 0xF == 0xE {
-            let v = self.read(self.cpu.rf.PC, 1)?[0];
-            self.cpu.rf.PC += 1;
-            v
-        } else {
-            self.cpu.rf[reg]
-        };
+        let (val, operand) = self.alu_operand(opcode)?;
 
-        let result = self.cpu.rf[reg].wrapping_sub(val);
+        //CP is just a SUB that throws away the result
+        let original = self.cpu.rf[A];
+        let result = original.wrapping_sub(val);
 
         self.cpu.rf.z_set(result == 0);
         self.cpu.rf.n_set(true);
-        //self.cpu.rf.h_set(false);
-        //self.cpu.rf.c_set(false);
+        self.cpu.rf.h_set((original & 0x0F) < (val & 0x0F));
+        self.cpu.rf.c_set(original < val);
 
-        debug!("CP {}", reg);
+        debug!("CP {}", operand);
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(alu_cycles(opcode));
     }
 
-    pub fn STRA16(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+    pub fn STRA16(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let lower = self.read(self.cpu.rf.PC, 1)?[0];
-        let upper = self.read(self.cpu.rf.PC + 1, 1)?[0];
+        let address = self.read16(self.cpu.rf.PC)?;
         self.cpu.rf.PC += 2;
-        let address = (upper as u16) << 8 | lower as u16;
 
-        self.write(address, &[self.cpu.rf.A])?;
+        self.write8(address, self.cpu.rf.A)?;
 
         debug!("LD (u16), A");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(16);
     }
-    pub fn LDA16(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+    pub fn LDA16(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
         self.cpu.rf.PC += 1;
 
-        let lower = self.read(self.cpu.rf.PC, 1)?[0];
-        let upper = self.read(self.cpu.rf.PC + 1, 1)?[0];
+        let address = self.read16(self.cpu.rf.PC)?;
         self.cpu.rf.PC += 2;
-        let address = (upper as u16) << 8 | lower as u16;
 
-        self.cpu.rf[A] = self.read(address, 1)?[0];
+        self.cpu.rf[A] = self.read8(address)?;
 
         debug!("LD A, (u16)");
 
-        return Ok(OPCODE_TIMINGS[opcode as usize]);
+        return Ok(16);
+    }
+
+    pub fn NOP(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        debug!("NOP");
+
+        return Ok(4);
+    }
+
+    pub fn STRSP16(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let address = self.read16(self.cpu.rf.PC)?;
+        self.cpu.rf.PC += 2;
+
+        self.write16(address, self.cpu.rf.SP)?;
+
+        debug!("LD ({:#04x}), SP", address);
+
+        return Ok(20);
+    }
+
+    pub fn ADD16(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let (log, val) = match opcode {
+            0x09 => ("ADD HL, BC", self.cpu.rf.BC_read()),
+            0x19 => ("ADD HL, DE", self.cpu.rf.DE_read()),
+            0x29 => ("ADD HL, HL", self.cpu.rf.HL_read()),
+            0x39 => ("ADD HL, SP", self.cpu.rf.SP),
+            _ => unreachable!("panicking in ADD16 on an unreachable opcode"),
+        };
+
+        let original = self.cpu.rf.HL_read();
+        let (result, carry) = original.overflowing_add(val);
+        self.cpu.rf.HL_write(result);
+
+        //zero untouched
+        self.cpu.rf.n_set(false);
+        //16 bit adds carry out of bit 11 for half carry and bit 15 for carry
+        self.cpu
+            .rf
+            .h_set((original & 0x0FFF) + (val & 0x0FFF) > 0x0FFF);
+        self.cpu.rf.c_set(carry);
+
+        debug!("{log}");
+
+        return Ok(8);
+    }
+
+    pub fn ADDSPimm(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let offset = self.read8(self.cpu.rf.PC)?;
+        self.cpu.rf.PC += 1;
+
+        let sp = self.cpu.rf.SP;
+        let result = sp.wrapping_add_signed(offset as i8 as i16);
+
+        //the flags for both of these come from an UNSIGNED add into the low byte of SP
+        self.cpu.rf.z_set(false);
+        self.cpu.rf.n_set(false);
+        self.cpu
+            .rf
+            .h_set((sp & 0x000F) + (offset as u16 & 0x000F) > 0x000F);
+        self.cpu
+            .rf
+            .c_set((sp & 0x00FF) + (offset as u16 & 0x00FF) > 0x00FF);
+
+        let log = if opcode == 0xE8 {
+            self.cpu.rf.SP = result;
+            format!("ADD SP, {}", offset as i8)
+        } else {
+            self.cpu.rf.HL_write(result);
+            format!("LD HL, SP+{}", offset as i8)
+        };
+
+        debug!("{log}");
+
+        return Ok(if opcode == 0xE8 { 16 } else { 12 });
+    }
+
+    pub fn LDSPHL(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        self.cpu.rf.SP = self.cpu.rf.HL_read();
+
+        debug!("LD SP, HL");
+
+        return Ok(8);
+    }
+
+    pub fn DAA(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        //fix up A back into packed BCD based on what the last add/sub did
+        let mut a = self.cpu.rf[A];
+        let mut carry = self.cpu.rf.c_get();
+        if !self.cpu.rf.n_get() {
+            //after an addition
+            if carry || a > 0x99 {
+                a = a.wrapping_add(0x60);
+                carry = true;
+            }
+            if self.cpu.rf.h_get() || (a & 0x0F) > 0x09 {
+                a = a.wrapping_add(0x06);
+            }
+        } else {
+            //after a subtraction
+            if carry {
+                a = a.wrapping_sub(0x60);
+            }
+            if self.cpu.rf.h_get() {
+                a = a.wrapping_sub(0x06);
+            }
+        }
+
+        self.cpu.rf[A] = a;
+        self.cpu.rf.z_set(a == 0);
+        //subtract untouched
+        self.cpu.rf.h_set(false);
+        self.cpu.rf.c_set(carry);
+
+        debug!("DAA");
+
+        return Ok(4);
+    }
+
+    pub fn CPL(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        self.cpu.rf[A] = !self.cpu.rf[A];
+        self.cpu.rf.n_set(true);
+        self.cpu.rf.h_set(true);
+
+        debug!("CPL");
+
+        return Ok(4);
+    }
+
+    pub fn SCFCCF(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let log = if opcode == 0x37 {
+            self.cpu.rf.c_set(true);
+            "SCF"
+        } else {
+            self.cpu.rf.c_set(!self.cpu.rf.c_get());
+            "CCF"
+        };
+        self.cpu.rf.n_set(false);
+        self.cpu.rf.h_set(false);
+
+        debug!("{log}");
+
+        return Ok(4);
+    }
+
+    pub fn DIEI(&mut self, opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        let log = if opcode == 0xF3 {
+            //DI is immeadiate, and also cancels an EI that hasnt kicked in yet
+            self.cpu.ime = false;
+            self.cpu.ime_pending = false;
+            "DI"
+        } else {
+            //EI only sets IME after the NEXT instruction has finished, step() takes care of that
+            self.cpu.ime_pending = true;
+            "EI"
+        };
+
+        debug!("{log}");
+
+        return Ok(4);
+    }
+
+    pub fn HALT(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        self.cpu.rf.PC += 1;
+
+        if !self.cpu.ime && self.io.interrupts.pending() != 0 {
+            //with IME off and something already pending, HALT exits straight away and trips the HALT bug
+            self.cpu.halt_bug = true;
+            debug!("HALT (bugged)");
+        } else {
+            self.status = SystemState::Halted;
+            debug!("HALT");
+        }
+
+        return Ok(4);
+    }
+
+    pub fn STOP(&mut self, _opcode: u8) -> Result<usize, ExecutionError> {
+        //STOP is 2 bytes long, the second one is ignored but still gets skipped over
+        self.cpu.rf.PC += 2;
+
+        //NOTE: on CGB, STOP with KEY1 armed does a speed switch instead of stopping. we're DMG only so for now
+        //it always just stops, but the speed switch needs to hook in right here
+        self.io.timer.reset_div();
+        //only a press that happens from here on ends STOP, a joypad request left over from before would wake us
+        //right back up
+        self.io.interrupts.IF &= !Interrupt::Joypad.bit();
+        self.status = SystemState::Stopped;
+        debug!("STOP");
+
+        return Ok(4);
     }
 }
 
@@ -1505,126 +1866,672 @@ FF00	FF7F	I/O Registers
 FF80	FFFE	High RAM (HRAM)
 FFFF	FFFF	Interrupt Enable register (IE)	*/
 
+//this is the bus as the cpu sees it. every access is an M cycle for the rest of the system (see scheduled_step).
+//OAM DMA locks the cpu out of the bus it is copying over (and OAM) while it runs, and then the rest goes straight
+//through to the real thing
+impl Bus for System {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
+        self.tick_bus_access();
+        if self.dma.blocks(address) {
+            return Ok(0xFF);
+        }
+        self.bus_read8(address)
+    }
+
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
+        self.tick_bus_access();
+        if self.dma.blocks(address) {
+            return Ok(());
+        }
+        self.bus_write8(address, value)
+    }
+}
+
 impl System {
-    //TOD: you're gonna need some nuance to support reading across different memory regions.
-    //what happens if you read from 0x3FFF with len >1
-    pub fn read(&mut self, address: u16, len: usize) -> Result<Vec<u8>, ExecutionError> {
-        trace!(
-            "receiving system read at address {:#04X} of length {:#04X}",
-            address,
-            len
-        );
+    fn bus_read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
+        trace!("receiving system read at address {:#04X}", address);
         match address {
             0x0000..=0x3FFF => {
-                let mut return_vec: Vec<u8> = Vec::new();
-
                 if self.io.bootrom_disable == 0 && address < 0x0100 {
-                    let end_address = address as usize + len;
-                    if end_address < 0x0100 {
-                        //read from bootrom
-                        Ok(self.boot_rom[address as usize..=address as usize + len].to_vec())
-                    } else {
-                        return_vec.append(&mut self.boot_rom[..].to_vec());
-                        return_vec.append(&mut self.cart.read(0x0100, end_address)?);
-                        Ok(return_vec)
-                    }
+                    //read from bootrom
+                    Ok(self.boot_rom[address as usize])
                 } else {
                     //read from cart rom bank 0
-                    self.cart.read(address, len)
+                    self.cart.read8(address)
                 }
             }
-            0x4000..=0x7FFF => unimplemented!("unimplemented read from cart rom bank 01~NN"),
+            0x4000..=0x7FFF => self.cart.read8(address),
             0x8000..=0x9FFF => {
-                //protect against reading off the end of vram
-                if (address as usize + len) > 0xA000 {
-                    return Err(ExecutionError::IllegalRead(address as usize));
+                if self.io.ppu.vram_locked() {
+                    return Ok(0xFF);
                 }
-                let address = address - 0x8000;
-                Ok(self.vram[address as usize..(address as usize + len)].to_vec())
+                Ok(self.vram[(address - 0x8000) as usize])
             }
-            0xA000..=0xBFFF => unimplemented!("unimplemented read from cart ram"),
-            0xC000..=0xCFFF => unimplemented!("unimplemented read from WRAM bank 0"),
-            0xD000..=0xDFFF => unimplemented!("unimplemented read from WRAM bank 1"),
-            0xE000..=0xFDFF => unimplemented!("unimplemented read from ECHO RAM"),
-            0xFE00..=0xFE9F => unimplemented!("unimplemented read from OAM"),
-            0xFEA0..=0xFEFF => unimplemented!("unimplemented read from UNUSABLE AREA"),
-            0xFF00..=0xFF7F => self.io.read(address, len),
-            0xFF80..=0xFFFE => {
-                //unimplemented!("unimplemented read from HRAM (what the fuck is this even used for lol)")
-                if (address as usize + len) > 0xFFFF {
-                    return Err(ExecutionError::IllegalRead(address as usize));
+            0xA000..=0xBFFF => self.cart.read8(address),
+            //on DMG both WRAM "banks" are fixed, so its really just one 8KiB chunk
+            0xC000..=0xDFFF => Ok(self.wram[(address - 0xC000) as usize]),
+            //echo ram mirrors C000-DDFF, the hardware just doesnt decode address bit 13 in here
+            0xE000..=0xFDFF => Ok(self.wram[(address - 0xE000) as usize]),
+            0xFE00..=0xFE9F => {
+                if self.io.ppu.oam_locked() {
+                    return Ok(0xFF);
+                }
+                Ok(self.oam[(address - 0xFE00) as usize])
+            }
+            //DMG reads 0x00 out of here, or 0xFF while the ppu has OAM locked
+            0xFEA0..=0xFEFF => {
+                if self.io.ppu.oam_locked() {
+                    return Ok(0xFF);
                 }
-                let address = address - 0xFF80;
-                Ok(self.hram[address as usize..(address as usize + len)].to_vec())
+                Ok(0x00)
             }
-            0xFFFF => unimplemented!("unimplemented read from IE reg"),
+            0xFF00..=0xFF7F => self.io.read8(address),
+            0xFF80..=0xFFFE => Ok(self.hram[(address - 0xFF80) as usize]),
+            0xFFFF => Ok(self.io.interrupts.IE),
         }
     }
 
-    fn write(&mut self, address: u16, data: &[u8]) -> Result<usize, ExecutionError> {
+    fn bus_write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
         trace!(
-            "receiving system write at address {:#04X} of length {:#04X}",
+            "receiving system write at address {:#04X} of value {:#02X}",
             address,
-            data.len()
+            value
         );
 
-        let res = match address {
+        match address {
             0x0000..=0x3FFF => {
                 if self.io.bootrom_disable == 0 && address < 0x0100 {
                     //write from bootrom?
                     //TODO: find out about legality of this. i dont think it should ever happen unless we write a
                     //self modifying bootrom?
                     panic!("tried to write to bootrom?")
-                    //Ok(self.boot_rom[address as usize..=address as usize + len].to_vec())
                 } else {
-                    //read from cart rom bank 0
-                    self.cart.write(address, data)
+                    //writes into "rom" are really writes to the mapper's registers
+                    self.cart.write8(address, value)
                 }
             }
-            0x4000..=0x7FFF => unimplemented!("unimplemented write to cart rom bank 01~NN"),
+            0x4000..=0x7FFF => self.cart.write8(address, value),
             0x8000..=0x9FFF => {
-                //protect against writing off the end of vram
-                if (address as usize + data.len()) > 0xA000 {
-                    self.comms
-                        .log_tx
-                        .send(format!(
-                            "address: {:#04x}, data len: {}",
-                            address,
-                            data.len()
-                        ))
-                        .unwrap();
-                    return Err(ExecutionError::IllegalWrite(address as usize));
+                if !self.io.ppu.vram_locked() {
+                    self.vram[(address - 0x8000) as usize] = value;
                 }
-
-                let address = address - 0x8000;
-                unsafe {
-                    let src_ptr = data.as_ptr();
-                    let dst_ptr = self.vram.as_mut_ptr().add(address as usize);
-                    std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, data.len());
+                Ok(())
+            }
+            0xA000..=0xBFFF => self.cart.write8(address, value),
+            0xC000..=0xDFFF => {
+                self.wram[(address - 0xC000) as usize] = value;
+                Ok(())
+            }
+            //writes to echo ram land in WRAM just like reads do
+            0xE000..=0xFDFF => {
+                self.wram[(address - 0xE000) as usize] = value;
+                Ok(())
+            }
+            0xFE00..=0xFE9F => {
+                if !self.io.ppu.oam_locked() {
+                    self.oam[(address - 0xFE00) as usize] = value;
                 }
-
-                Ok(data.len())
+                Ok(())
+            }
+            //writes to the unusable area go nowhere
+            0xFEA0..=0xFEFF => Ok(()),
+            //the register lives in the ppu, but the transfer needs the whole bus so it runs out here
+            0xFF46 => {
+                self.dma.start(value);
+                self.io.write8(address, value)
             }
-            0xA000..=0xBFFF => unimplemented!("unimplemented write to cart ram"),
-            0xC000..=0xCFFF => unimplemented!("unimplemented write to WRAM bank 0"),
-            0xD000..=0xDFFF => unimplemented!("unimplemented write to WRAM bank 1"),
-            0xE000..=0xFDFF => unimplemented!("unimplemented write to ECHO RAM"),
-            0xFE00..=0xFE9F => unimplemented!("unimplemented write to OAM"),
-            0xFEA0..=0xFEFF => unimplemented!("unimplemented write to UNUSABLE AREA"),
-            0xFF00..=0xFF7F => self.io.write(address, data),
+            0xFF00..=0xFF7F => self.io.write8(address, value),
             0xFF80..=0xFFFE => {
-                //unimplemented!("unimplemented write to HRAM (what the fuck is this even used for lol)")
-                let address = address - 0xFF80;
-                unsafe {
-                    let src_ptr = data.as_ptr();
-                    let dst_ptr = self.hram.as_mut_ptr().add(address as usize);
-                    std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, data.len());
-                }
-                Ok(data.len())
+                self.hram[(address - 0xFF80) as usize] = value;
+                Ok(())
+            }
+            0xFFFF => {
+                self.io.interrupts.IE = value;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cart::{LoadOptions, NINTENDO_LOGO};
+    use crate::interrupt::Interrupt;
+    use std::sync::mpsc::channel;
+
+    #[rustfmt::skip]
+    //what each opcode should cost IN T_CYCLES, checked against what its handler returns
+    //NOTE: for conditional branches (JR/JP/CALL/RET cc) this is the NOT taken cost, the taken cost is checked
+    //against BRANCH_TAKEN_TIMINGS below. 0 means illegal opcode (hard locks the real cpu) and doesnt get checked
+    static REFERENCE_TIMINGS: [usize; 256] = [
+    //  0   1   2   3   4   5   6   7   8   9   A   B   C   D   E   F 
+        4,  12, 8,  8,  4,  4,  8,  4,  20, 8,  8,  8,  4,  4,  8,  4,  //0x0
+        4,  12, 8,  8,  4,  4,  8,  4,  12, 8,  8,  8,  4,  4,  8,  4,  //0x1
+        8,  12, 8,  8,  4,  4,  8,  4,  8,  8,  8,  8,  4,  4,  8,  4,  //0x2
+        8,  12, 8,  8,  12, 12, 12, 4,  8,  8,  8,  8,  4,  4,  8,  4,  //0x3
+        4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,  //0x4
+        4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,  //0x5
+        4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,  //0x6
+        8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4,  //0x7
+        4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,  //0x8
+        4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,  //0x9
+        4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,  //0xA
+        4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,  //0xB
+        8,  12, 12, 16, 12, 16, 8,  16, 8,  16, 12, 4,  12, 24, 8,  16, //0xC
+        8,  12, 12, 0,  12, 16, 8,  16, 8,  16, 12, 0,  12, 0,  8,  16, //0xD
+        12, 12, 8,  0,  0,  16, 8,  16, 16, 4,  16, 0,  0,  0,  8,  16, //0xE
+        12, 12, 8,  4,  0,  16, 8,  16, 12, 8,  16, 4,  0,  0,  8,  16, //0xF
+    ];
+
+    #[rustfmt::skip]
+    //opcode timings IN T_CYCLES
+    //NOTE: these do NOT include the 4 cycles for fetching the 0xCB prefix, thats added on in execute_op
+    static CB_REFERENCE_TIMINGS: [usize; 256] = [
+    //  0  1  2  3  4  5  6   7  8  9  A  B  C  D  E   F 
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x0
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x1
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x2
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x3
+        4, 4, 4, 4, 4, 4, 8,  4, 4, 4, 4, 4, 4, 4, 8,  4, //0x4
+        4, 4, 4, 4, 4, 4, 8,  4, 4, 4, 4, 4, 4, 4, 8,  4, //0x5
+        4, 4, 4, 4, 4, 4, 8,  4, 4, 4, 4, 4, 4, 4, 8,  4, //0x6
+        4, 4, 4, 4, 4, 4, 8,  4, 4, 4, 4, 4, 4, 4, 8,  4, //0x7
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x8
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0x9
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0xA
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0xB
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0xC
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0xD
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0xE
+        4, 4, 4, 4, 4, 4, 12, 4, 4, 4, 4, 4, 4, 4, 12, 4, //0xF
+    ];
+
+    //what the conditional branches cost when they ARE taken, REFERENCE_TIMINGS has the not taken cost
+    static BRANCH_TAKEN_TIMINGS: [(u8, usize); 16] = [
+        (0x20, 12),
+        (0x28, 12),
+        (0x30, 12),
+        (0x38, 12),
+        (0xC2, 16),
+        (0xCA, 16),
+        (0xD2, 16),
+        (0xDA, 16),
+        (0xC4, 24),
+        (0xCC, 24),
+        (0xD4, 24),
+        (0xDC, 24),
+        (0xC0, 20),
+        (0xC8, 20),
+        (0xD0, 20),
+        (0xD8, 20),
+    ];
+
+    //builds a system with the program sitting in the bootrom at 0x0000 and every pointer register aimed at
+    //memory we can actually touch (VRAM for BC/DE/HL/imm16, HRAM for SP and the FF00+n forms)
+    fn test_system(program: &[u8], flags: u8) -> System {
+        let (log_tx, _) = channel();
+        let (screen_tx, _) = channel();
+        let (command_tx, _) = channel();
+        let (_, command_rx) = channel();
+        let (cpu_tx, _) = channel();
+
+        let mut rom = vec![0; 0x8000];
+        //just enough of a header to get past the boot checks
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x014D] = 0xE7;
+        let cart = Cart::new(&mut std::io::Cursor::new(rom), &LoadOptions::default()).unwrap();
+
+        let mut boot_rom = [0; 0x100];
+        boot_rom[..program.len()].copy_from_slice(program);
+
+        let mut sys = System::new(
+            log_tx,
+            screen_tx,
+            command_tx,
+            command_rx,
+            cpu_tx,
+            Box::new(|| {}),
+            Cpu::new().unwrap(),
+            cart,
+            Io::new(),
+            boot_rom,
+        );
+        sys.cpu.rf.BC_write(0x8080);
+        sys.cpu.rf.DE_write(0x8080);
+        sys.cpu.rf.HL_write(0x8080);
+        sys.cpu.rf.SP = 0xFFF0;
+        sys.cpu.rf.F = flags;
+        sys
+    }
+
+    //flag register value that makes the condition encoded in bits 3-4 of a conditional branch pass or fail
+    fn branch_flags(opcode: u8, taken: bool) -> u8 {
+        let (flag, set_means_taken) = match (opcode >> 3) & 0b11 {
+            0 => (0b1000_0000, false), //NZ
+            1 => (0b1000_0000, true),  //Z
+            2 => (0b0001_0000, false), //NC
+            _ => (0b0001_0000, true),  //C
+        };
+        if taken == set_means_taken {
+            flag
+        } else {
+            0
+        }
+    }
+
+    fn is_branch(opcode: u8) -> bool {
+        BRANCH_TAKEN_TIMINGS.iter().any(|(op, _)| *op == opcode)
+    }
+
+    #[test]
+    fn opcode_timings_match_reference() {
+        for opcode in 0..=0xFFu8 {
+            //illegal opcodes and the CB prefix are covered elsewhere
+            if REFERENCE_TIMINGS[opcode as usize] == 0 || opcode == 0xCB {
+                continue;
             }
-            0xFFFF => unimplemented!("unimplemented write to IE reg"),
+            let flags = if is_branch(opcode) {
+                branch_flags(opcode, false)
+            } else {
+                0
+            };
+            let mut sys = test_system(&[opcode, 0x80, 0x80], flags);
+            let cycles = sys.execute_op(opcode).unwrap();
+            assert_eq!(
+                cycles, REFERENCE_TIMINGS[opcode as usize],
+                "opcode {:#04X} took {} cycles",
+                opcode, cycles
+            );
+        }
+    }
+
+    #[test]
+    fn branch_taken_timings_match_reference() {
+        for (opcode, expected) in BRANCH_TAKEN_TIMINGS {
+            let mut sys = test_system(&[opcode, 0x80, 0x80], branch_flags(opcode, true));
+            let cycles = sys.execute_op(opcode).unwrap();
+            assert_eq!(
+                cycles, expected,
+                "taken opcode {:#04X} took {} cycles",
+                opcode, cycles
+            );
+        }
+    }
+
+    #[test]
+    fn cb_opcode_timings_match_reference() {
+        for opcode in 0..=0xFFu8 {
+            let mut sys = test_system(&[0xCB, opcode], 0);
+            let cycles = sys.execute_op(0xCB).unwrap();
+            //reference table doesnt include the prefix fetch
+            assert_eq!(
+                cycles,
+                4 + CB_REFERENCE_TIMINGS[opcode as usize],
+                "opcode 0xCB{:02X} took {} cycles",
+                opcode,
+                cycles
+            );
+        }
+    }
+
+    #[test]
+    fn interrupt_dispatch() {
+        let mut sys = test_system(&[0x00], 0);
+        sys.cpu.ime = true;
+        sys.io.interrupts.IE = 0xFF;
+        sys.io.interrupts.request(Interrupt::Timer);
+        sys.io.interrupts.request(Interrupt::Serial);
+
+        //2 wait states, 2 pushes and the jump
+        assert_eq!(sys.step().unwrap(), 20);
+        assert_eq!(sys.cpu.rf.PC, 0x0050);
+        assert_eq!(sys.cpu.rf.SP, 0xFFEE);
+        assert!(!sys.cpu.ime);
+        //only the one that got serviced is acknowledged
+        assert_eq!(sys.io.interrupts.IF, Interrupt::Serial.bit());
+    }
+
+    #[test]
+    fn ei_takes_effect_after_the_next_instruction() {
+        //EI, NOP: the NOP still runs before the dispatch
+        let mut sys = test_system(&[0xFB, 0x00, 0x00], 0);
+        sys.io.interrupts.IE = 0xFF;
+        sys.io.interrupts.request(Interrupt::VBlank);
+        sys.step().unwrap();
+        assert!(!sys.cpu.ime);
+        sys.step().unwrap();
+        assert_eq!(sys.cpu.rf.PC, 0x0002);
+        assert!(sys.cpu.ime);
+        assert_eq!(sys.step().unwrap(), 20);
+        assert_eq!(sys.cpu.rf.PC, 0x0040);
+
+        //EI, DI: the DI lands before IME ever gets set, so the pending interrupt is never taken
+        let mut sys = test_system(&[0xFB, 0xF3, 0x00], 0);
+        sys.io.interrupts.IE = 0xFF;
+        sys.io.interrupts.request(Interrupt::VBlank);
+        for _ in 0..3 {
+            assert_eq!(sys.step().unwrap(), 4);
+        }
+        assert_eq!(sys.cpu.rf.PC, 0x0003);
+        assert!(!sys.cpu.ime);
+    }
+
+    #[test]
+    fn interrupt_register_masks() {
+        let mut sys = test_system(&[], 0);
+        //only the bottom 5 bits of IF exist, the rest read as 1
+        sys.write8(0xFF0F, 0x00).unwrap();
+        assert_eq!(sys.read8(0xFF0F).unwrap(), 0xE0);
+        sys.write8(0xFF0F, 0xFF).unwrap();
+        assert_eq!(sys.io.interrupts.IF, 0x1F);
+        //IE is a plain 8 bit register
+        sys.write8(0xFFFF, 0xFF).unwrap();
+        assert_eq!(sys.read8(0xFFFF).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn hl_store_wraps() {
+        //LD (HL+), A at the top of memory, writing IE on the way
+        let mut sys = test_system(&[], 0);
+        sys.cpu.rf.A = 0x15;
+        sys.cpu.rf.HL_write(0xFFFF);
+        sys.execute_op(0x22).unwrap();
+        assert_eq!(sys.cpu.rf.HL_read(), 0x0000);
+        assert_eq!(sys.io.interrupts.IE, 0x15);
+
+        //LD (HL-), A at the bottom, which is a (ignored) write to cart rom
+        let mut sys = test_system(&[], 0);
+        sys.io.bootrom_disable = 1;
+        sys.cpu.rf.HL_write(0x0000);
+        sys.execute_op(0x32).unwrap();
+        assert_eq!(sys.cpu.rf.HL_read(), 0xFFFF);
+    }
+
+    #[test]
+    fn stop_ignores_stale_joypad_request() {
+        //STOP, then a NOP to land on once we wake up
+        let mut sys = test_system(&[0x10, 0x00, 0x00], 0);
+        sys.io.interrupts.request(Interrupt::Joypad);
+        //select the action buttons so a press actually reaches the lines
+        sys.io.joypad.write(0x10, &mut sys.io.interrupts);
+
+        sys.step().unwrap();
+        assert!(matches!(sys.status, SystemState::Stopped));
+        sys.step().unwrap();
+        assert!(matches!(sys.status, SystemState::Stopped));
+
+        sys.io
+            .joypad
+            .set_button(Button::A, true, &mut sys.io.interrupts);
+        sys.step().unwrap();
+        assert!(matches!(sys.status, SystemState::Running));
+        assert_eq!(sys.cpu.rf.PC, 0x0003);
+    }
+
+    //each bus access ticks an M cycle under run_frame, so no instruction can touch the bus more often than its
+    //timing allows or the rest of the system would run ahead of the cpu
+    #[test]
+    fn bus_accesses_fit_in_timings() {
+        let check = |program: &[u8], flags: u8| {
+            let mut sys = test_system(program, flags);
+            //the opcode fetch happens in step, so count it here
+            sys.step_m_cycles = Some(1);
+            let cycles = sys.execute_op(program[0]).unwrap();
+            let accesses = sys.step_m_cycles.take().unwrap();
+            assert!(
+                accesses <= cycles / 4,
+                "opcode {:02X?} made {} bus accesses in {} cycles",
+                program,
+                accesses,
+                cycles
+            );
         };
+        for opcode in 0..=0xFFu8 {
+            if REFERENCE_TIMINGS[opcode as usize] == 0 || opcode == 0xCB {
+                continue;
+            }
+            for taken in [false, true] {
+                let flags = if is_branch(opcode) {
+                    branch_flags(opcode, taken)
+                } else {
+                    0
+                };
+                check(&[opcode, 0x80, 0x80], flags);
+            }
+        }
+        for opcode in 0..=0xFFu8 {
+            check(&[0xCB, opcode], 0);
+        }
+    }
+
+    fn read_stack(sys: &mut System, offset: u16) -> u8 {
+        let sp = sys.cpu.rf.SP;
+        sys.read8(sp.wrapping_add(offset)).unwrap()
+    }
+
+    #[test]
+    fn nested_calls_leave_return_addresses_on_stack() {
+        let mut program = vec![0; 0x100];
+        //0x00: CALL 0x0080
+        program[0x00..0x03].copy_from_slice(&[0xCD, 0x80, 0x00]);
+        //0x80: CALL 0x00C0
+        program[0x80..0x83].copy_from_slice(&[0xCD, 0xC0, 0x00]);
+        //0xC0: RET
+        program[0xC0] = 0xC9;
+        //0x83: RET
+        program[0x83] = 0xC9;
+        let mut sys = test_system(&program, 0);
+
+        sys.step().unwrap();
+        sys.step().unwrap();
+        assert_eq!(sys.cpu.rf.PC, 0x00C0);
+        assert_eq!(sys.cpu.rf.SP, 0xFFF0 - 4);
+        //innermost return address on top, low byte first
+        assert_eq!(read_stack(&mut sys, 0), 0x83);
+        assert_eq!(read_stack(&mut sys, 1), 0x00);
+        assert_eq!(read_stack(&mut sys, 2), 0x03);
+        assert_eq!(read_stack(&mut sys, 3), 0x00);
+
+        sys.step().unwrap();
+        assert_eq!(sys.cpu.rf.PC, 0x0083);
+        sys.step().unwrap();
+        assert_eq!(sys.cpu.rf.PC, 0x0003);
+        assert_eq!(sys.cpu.rf.SP, 0xFFF0);
+    }
+
+    #[test]
+    fn conditional_calls_push_full_return_address() {
+        let mut program = vec![0; 0x100];
+        //0xA0: CALL NZ,0x0010 / CALL NC,0x0010 / CALL Z,0x0010 / CALL C,0x0010
+        for (i, opcode) in [0xC4, 0xD4, 0xCC, 0xDC].into_iter().enumerate() {
+            let base = 0xA0 + i * 3;
+            program[base..base + 3].copy_from_slice(&[opcode, 0x10, 0x00]);
+        }
+
+        for (i, opcode) in [0xC4u8, 0xD4, 0xCC, 0xDC].into_iter().enumerate() {
+            let mut sys = test_system(&program, branch_flags(opcode, true));
+            sys.cpu.rf.PC = 0xA0 + (i as u16) * 3;
+            let return_address = sys.cpu.rf.PC + 3;
+
+            sys.step().unwrap();
+            assert_eq!(sys.cpu.rf.PC, 0x0010, "opcode {:#04X}", opcode);
+            assert_eq!(sys.cpu.rf.SP, 0xFFF0 - 2, "opcode {:#04X}", opcode);
+            assert_eq!(read_stack(&mut sys, 0), (return_address & 0xFF) as u8);
+            assert_eq!(read_stack(&mut sys, 1), (return_address >> 8) as u8);
+        }
+    }
+
+    #[test]
+    fn rst_and_reti_round_trip() {
+        let mut program = vec![0; 0x100];
+        //0x50: RST 0x38
+        program[0x50] = 0xFF;
+        //0x38: RETI
+        program[0x38] = 0xD9;
+        let mut sys = test_system(&program, 0);
+        sys.cpu.rf.PC = 0x50;
+
+        sys.step().unwrap();
+        assert_eq!(sys.cpu.rf.PC, 0x38);
+        assert_eq!(read_stack(&mut sys, 0), 0x51);
+        assert_eq!(read_stack(&mut sys, 1), 0x00);
+
+        sys.step().unwrap();
+        assert_eq!(sys.cpu.rf.PC, 0x51);
+        assert_eq!(sys.cpu.rf.SP, 0xFFF0);
+        assert!(sys.cpu.ime);
+    }
+
+    #[test]
+    fn push_pop_round_trip() {
+        //PUSH BC, PUSH DE, POP HL, POP AF
+        let mut sys = test_system(&[0xC5, 0xD5, 0xE1, 0xF1], 0);
+        sys.cpu.rf.BC_write(0x12FF);
+        sys.cpu.rf.DE_write(0xBEEF);
+
+        sys.step().unwrap();
+        sys.step().unwrap();
+        assert_eq!(read_stack(&mut sys, 0), 0xEF);
+        assert_eq!(read_stack(&mut sys, 1), 0xBE);
+        assert_eq!(read_stack(&mut sys, 2), 0xFF);
+        assert_eq!(read_stack(&mut sys, 3), 0x12);
+
+        sys.step().unwrap();
+        assert_eq!(sys.cpu.rf.HL_read(), 0xBEEF);
+        sys.step().unwrap();
+        //low nibble of F is always zero
+        assert_eq!(sys.cpu.rf.AF_read(), 0x12F0);
+        assert_eq!(sys.cpu.rf.SP, 0xFFF0);
+    }
+
+    #[test]
+    fn interrupt_dispatch_pushes_pc() {
+        let mut sys = test_system(&[0x00], 0);
+        sys.cpu.rf.PC = 0x1234;
+        sys.cpu.ime = true;
+        sys.io.interrupts.IE = Interrupt::Timer.bit();
+        sys.io.interrupts.request(Interrupt::Timer);
+
+        assert_eq!(sys.step().unwrap(), 20);
+        assert_eq!(sys.cpu.rf.PC, Interrupt::Timer.vector());
+        assert_eq!(read_stack(&mut sys, 0), 0x34);
+        assert_eq!(read_stack(&mut sys, 1), 0x12);
+        assert!(!sys.cpu.ime);
+        assert_eq!(sys.io.interrupts.IF & Interrupt::Timer.bit(), 0);
+    }
+
+    #[test]
+    fn echo_ram_mirrors_wram() {
+        let mut sys = test_system(&[], 0);
+        sys.write8(0xC000, 0x12).unwrap();
+        assert_eq!(sys.read8(0xE000).unwrap(), 0x12);
+        sys.write8(0xDDFF, 0x34).unwrap();
+        assert_eq!(sys.read8(0xFDFF).unwrap(), 0x34);
+        //and the other way around
+        sys.write8(0xF123, 0x56).unwrap();
+        assert_eq!(sys.read8(0xD123).unwrap(), 0x56);
+        sys.write8(0xE000, 0x78).unwrap();
+        assert_eq!(sys.read8(0xC000).unwrap(), 0x78);
+    }
+
+    #[test]
+    fn oam_and_unusable_area() {
+        let mut sys = test_system(&[], 0);
+        sys.write8(0xFE00, 0x11).unwrap();
+        sys.write8(0xFE9F, 0x22).unwrap();
+        assert_eq!(sys.read8(0xFE00).unwrap(), 0x11);
+        assert_eq!(sys.read8(0xFE9F).unwrap(), 0x22);
+
+        //writes past the end of OAM go nowhere, and reads there are always 0
+        sys.write8(0xFEA0, 0x33).unwrap();
+        sys.write8(0xFEFF, 0x44).unwrap();
+        assert_eq!(sys.read8(0xFEA0).unwrap(), 0x00);
+        assert_eq!(sys.read8(0xFEFF).unwrap(), 0x00);
+        assert_eq!(sys.oam[159], 0x22);
+    }
+
+    #[test]
+    fn wide_accesses_across_regions() {
+        let mut sys = test_system(&[0xAB], 0);
+        //end of WRAM into the start of echo ram, which is the start of WRAM again
+        sys.wram[0x1FFF] = 0x34;
+        sys.wram[0x0000] = 0x12;
+        assert_eq!(sys.read16(0xDFFF).unwrap(), 0x1234);
+
+        //end of OAM into the unusable area
+        sys.oam[159] = 0x56;
+        assert_eq!(sys.read16(0xFE9F).unwrap(), 0x0056);
+        sys.write16(0xFE9F, 0xFFAA).unwrap();
+        assert_eq!(sys.oam[159], 0xAA);
+
+        //end of HRAM into IE
+        sys.write16(0xFFFE, 0x1F78).unwrap();
+        assert_eq!(sys.hram[126], 0x78);
+        assert_eq!(sys.io.interrupts.IE, 0x1F);
+
+        //and off the top of memory back around to the boot rom
+        assert_eq!(sys.read16(0xFFFF).unwrap(), 0xAB1F);
+    }
+
+    #[test]
+    fn dma_locks_only_its_own_bus() {
+        let mut sys = test_system(&[0x00], 0);
+        sys.write8(0xC000, 0x12).unwrap();
+        sys.write8(0x8000, 0x34).unwrap();
+        sys.write8(0xFF80, 0x56).unwrap();
+
+        sys.write8(0xFF46, 0xC0).unwrap();
+        //startup cycle, then the first byte
+        sys.tick(8);
+        assert_eq!(sys.read8(0xC000).unwrap(), 0xFF);
+        assert_eq!(sys.read8(0x0000).unwrap(), 0xFF);
+        assert_eq!(sys.read8(0xFE00).unwrap(), 0xFF);
+        assert_eq!(sys.read8(0x8000).unwrap(), 0x34);
+        assert_eq!(sys.read8(0xFF80).unwrap(), 0x56);
+        sys.tick(4 * 159);
+        assert_eq!(sys.read8(0xC000).unwrap(), 0x12);
+
+        //copying out of vram frees up the external bus instead
+        sys.write8(0xFF46, 0x80).unwrap();
+        sys.tick(8);
+        assert_eq!(sys.read8(0xC000).unwrap(), 0x12);
+        assert_eq!(sys.read8(0x8000).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn dma_restart_mid_transfer() {
+        let mut sys = test_system(&[0x00], 0);
+        for i in 0..160 {
+            sys.write8(0xC000 + i, 0xAA).unwrap();
+            sys.write8(0xC100 + i, i as u8).unwrap();
+        }
+
+        sys.write8(0xFF46, 0xC0).unwrap();
+        sys.tick(4 * 20);
+        assert_eq!(sys.oam[0], 0xAA);
+        //FF46 is still reachable mid transfer, and starts over from the new source
+        sys.write8(0xFF46, 0xC1).unwrap();
+        sys.tick(4 * 161);
+        for i in 0..160 {
+            assert_eq!(sys.oam[i], i as u8);
+        }
+    }
 
-        res
+    #[test]
+    fn cpu_locked_out_of_vram_in_mode_3() {
+        let mut sys = test_system(&[0x00], 0);
+        sys.write8(0x8000, 0x12).unwrap();
+        sys.write8(0xFF40, 0x91).unwrap();
+        //past the first line, then into the oam scan and mode 3 of the next
+        sys.tick(456 + 84);
+        assert_eq!(sys.read8(0xFE00).unwrap(), 0xFF);
+        sys.tick(8);
+        assert_eq!(sys.io.ppu.mode(), crate::ppu::PpuMode::Transfer);
+        assert_eq!(sys.read8(0x8000).unwrap(), 0xFF);
+        sys.write8(0x8000, 0x34).unwrap();
+        //hblank
+        sys.tick(300);
+        assert_eq!(sys.read8(0x8000).unwrap(), 0x12);
     }
 }
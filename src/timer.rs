@@ -0,0 +1,266 @@
+use crate::bus::Bus;
+use crate::interrupt::{Interrupt, InterruptController};
+use crate::system::ExecutionError;
+
+/*$FF04	DIV	    Divider register	R/W	All
+$FF05	TIMA	Timer counter	    R/W	All
+$FF06	TMA	    Timer modulo	    R/W	All
+$FF07	TAC	    Timer control	    R/W	All
+
+TAC
+Bit 2    Enable
+Bits 1-0 Clock select
+         00: CPU Clock / 1024 (4096 Hz)     system counter bit 9
+         01: CPU Clock / 16   (262144 Hz)   system counter bit 3
+         10: CPU Clock / 64   (65536 Hz)    system counter bit 5
+         11: CPU Clock / 256  (16384 Hz)    system counter bit 7
+
+there is really just one 16 bit counter going up every T cycle, and DIV is the top 8 bits of it. TIMA doesnt have
+its own clock, it goes up whenever (selected counter bit AND timer enable) goes from 1 to 0. all of the weird
+stuff falls out of that one AND gate:
+  writing DIV zeroes the whole counter, so if the selected bit was 1 TIMA goes up
+  writing TAC can turn the signal off (disabling, or switching to a bit that is 0), which also counts as a
+  falling edge and bumps TIMA*/
+
+//what happens after TIMA overflows. it reads 00 for one M cycle, then TMA gets loaded and the interrupt fires
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Overflow {
+    #[default]
+    None,
+    //overflowed last cycle, TIMA is sitting at 00. writing TIMA now cancels the reload
+    Pending,
+    //TMA just got loaded. writes to TIMA this cycle lose to the reload, writes to TMA go through to TIMA too
+    Reloading,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Default)]
+pub struct Timer {
+    counter: u16,
+    TIMA: u8,
+    TMA: u8,
+    TAC: u8,
+    overflow: Overflow,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    //runs the timer for some number of T cycles, a M cycle at a time
+    pub fn tick(&mut self, cycles: usize, interrupts: &mut InterruptController) {
+        for _ in 0..cycles / 4 {
+            self.step(interrupts);
+        }
+    }
+
+    fn step(&mut self, interrupts: &mut InterruptController) {
+        match self.overflow {
+            Overflow::Pending => {
+                self.TIMA = self.TMA;
+                interrupts.request(Interrupt::Timer);
+                self.overflow = Overflow::Reloading;
+            }
+            Overflow::Reloading => self.overflow = Overflow::None,
+            Overflow::None => {}
+        }
+
+        let before = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+        if before && !self.signal() {
+            self.increment_tima();
+        }
+    }
+
+    //the selected counter bit AND'd with the enable bit, TIMA counts the falling edges of this
+    fn signal(&self) -> bool {
+        let bit = match self.TAC & 0b11 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            0b11 => 7,
+            _ => unreachable!(),
+        };
+        (self.TAC & 0b100) != 0 && (self.counter >> bit) & 1 != 0
+    }
+
+    fn increment_tima(&mut self) {
+        let (value, overflowed) = self.TIMA.overflowing_add(1);
+        self.TIMA = value;
+        if overflowed {
+            self.overflow = Overflow::Pending;
+        }
+    }
+
+    //STOP resets the divider the same way writing to it does
+    pub fn reset_div(&mut self) {
+        let before = self.signal();
+        self.counter = 0;
+        if before {
+            self.increment_tima();
+        }
+    }
+}
+
+impl Bus for Timer {
+    fn read8(&mut self, address: u16) -> Result<u8, ExecutionError> {
+        let value = match address {
+            0xFF04 => (self.counter >> 8) as u8,
+            0xFF05 => self.TIMA,
+            0xFF06 => self.TMA,
+            //only the low 3 bits exist
+            0xFF07 => self.TAC | 0b1111_1000,
+            _ => unreachable!("timer trying to service a READ outside of its memory mapping range"),
+        };
+        Ok(value)
+    }
+
+    fn write8(&mut self, address: u16, value: u8) -> Result<(), ExecutionError> {
+        match address {
+            //any write zeroes it, the value doesnt matter
+            0xFF04 => self.reset_div(),
+            0xFF05 => match self.overflow {
+                Overflow::Pending => {
+                    self.TIMA = value;
+                    self.overflow = Overflow::None;
+                }
+                Overflow::Reloading => {}
+                Overflow::None => self.TIMA = value,
+            },
+            0xFF06 => {
+                self.TMA = value;
+                if self.overflow == Overflow::Reloading {
+                    self.TIMA = value;
+                }
+            }
+            0xFF07 => {
+                let before = self.signal();
+                self.TAC = value & 0b111;
+                if before && !self.signal() {
+                    self.increment_tima();
+                }
+            }
+            _ => {
+                unreachable!("timer trying to service a WRITE outside of its memory mapping range")
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tima(timer: &mut Timer) -> u8 {
+        timer.read8(0xFF05).unwrap()
+    }
+
+    #[test]
+    fn div_counts_every_256_cycles() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.tick(256 * 3 + 252, &mut interrupts);
+        assert_eq!(timer.read8(0xFF04).unwrap(), 3);
+        timer.write8(0xFF04, 0x42).unwrap();
+        assert_eq!(timer.read8(0xFF04).unwrap(), 0);
+    }
+
+    #[test]
+    fn tima_rates() {
+        for (tac, period) in [(0b100, 1024), (0b101, 16), (0b110, 64), (0b111, 256)] {
+            let mut timer = Timer::new();
+            let mut interrupts = InterruptController::new();
+            timer.write8(0xFF07, tac).unwrap();
+            timer.tick(period * 10 - 4, &mut interrupts);
+            assert_eq!(tima(&mut timer), 9, "TAC {:#05b}", tac);
+            timer.tick(4, &mut interrupts);
+            assert_eq!(tima(&mut timer), 10, "TAC {:#05b}", tac);
+        }
+    }
+
+    #[test]
+    fn overflow_reloads_a_cycle_late() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.write8(0xFF06, 0x80).unwrap();
+        timer.write8(0xFF05, 0xFF).unwrap();
+        timer.write8(0xFF07, 0b101).unwrap();
+        timer.tick(16, &mut interrupts);
+        //sits at 00 for a cycle before TMA comes in
+        assert_eq!(tima(&mut timer), 0x00);
+        assert_eq!(interrupts.IF, 0);
+        timer.tick(4, &mut interrupts);
+        assert_eq!(tima(&mut timer), 0x80);
+        assert_eq!(interrupts.IF, Interrupt::Timer.bit());
+    }
+
+    #[test]
+    fn tima_write_cancels_pending_reload() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.write8(0xFF06, 0x80).unwrap();
+        timer.write8(0xFF05, 0xFF).unwrap();
+        timer.write8(0xFF07, 0b101).unwrap();
+        timer.tick(16, &mut interrupts);
+        timer.write8(0xFF05, 0x10).unwrap();
+        timer.tick(4, &mut interrupts);
+        assert_eq!(tima(&mut timer), 0x10);
+        assert_eq!(interrupts.IF, 0);
+    }
+
+    #[test]
+    fn tima_write_loses_to_reload() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.write8(0xFF06, 0x80).unwrap();
+        timer.write8(0xFF05, 0xFF).unwrap();
+        timer.write8(0xFF07, 0b101).unwrap();
+        timer.tick(20, &mut interrupts);
+        //the cycle TMA got loaded, TIMA writes are dropped and TMA writes go straight through
+        timer.write8(0xFF05, 0x10).unwrap();
+        assert_eq!(tima(&mut timer), 0x80);
+        timer.write8(0xFF06, 0x33).unwrap();
+        assert_eq!(tima(&mut timer), 0x33);
+    }
+
+    #[test]
+    fn div_write_falling_edge() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.write8(0xFF07, 0b101).unwrap();
+        //counter bit 3 is set
+        timer.tick(8, &mut interrupts);
+        assert_eq!(tima(&mut timer), 0);
+        timer.write8(0xFF04, 0x00).unwrap();
+        assert_eq!(tima(&mut timer), 1);
+
+        //with the bit clear nothing happens
+        timer.tick(4, &mut interrupts);
+        timer.write8(0xFF04, 0x00).unwrap();
+        assert_eq!(tima(&mut timer), 1);
+    }
+
+    #[test]
+    fn tac_write_falling_edge() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.write8(0xFF07, 0b101).unwrap();
+        timer.tick(8, &mut interrupts);
+        //disabling while the selected bit is high
+        timer.write8(0xFF07, 0b001).unwrap();
+        assert_eq!(tima(&mut timer), 1);
+
+        //switching from a high bit (3) to a low one (9)
+        timer.write8(0xFF07, 0b101).unwrap();
+        timer.write8(0xFF07, 0b100).unwrap();
+        assert_eq!(tima(&mut timer), 2);
+
+        //switching from a low bit to a high one is a rising edge, which does nothing
+        timer.write8(0xFF07, 0b101).unwrap();
+        assert_eq!(tima(&mut timer), 2);
+    }
+}